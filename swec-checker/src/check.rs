@@ -0,0 +1,62 @@
+use async_trait::async_trait;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+mod dns;
+mod http;
+mod ping;
+mod tcp;
+pub use dns::Dns;
+pub use http::{Http, HttpExpectation, HttpRequest};
+pub use ping::Ping;
+pub use tcp::Tcp;
+
+/// A way of checking a service, e.g. over HTTP.
+/// Protocols are added by implementing this and registering a constructor for them in a
+/// `Registry`.
+#[async_trait]
+pub trait Check: Send + Sync {
+    /// Check the service once, giving up after `timeout`.
+    async fn check(&self, timeout: Duration) -> swec_core::Status;
+
+    /// The URL of the service, if it has one, to put in the checker's spec.
+    fn url(&self) -> Option<String> {
+        None
+    }
+}
+
+type Constructor = Box<dyn Fn(&str) -> Result<Box<dyn Check>, String> + Send + Sync>;
+
+/// The protocols checks can be created for, by prefix.
+/// Checks are given as `<prefix>#<target>`, for example `http#https://example.com`, and the
+/// target is passed to the constructor registered for the prefix.
+#[derive(Default)]
+pub struct Registry {
+    constructors: BTreeMap<String, Constructor>,
+}
+
+impl Registry {
+    /// Register the constructor of checks for the given prefix, replacing any previous one.
+    pub fn register<C: Check + 'static>(
+        &mut self,
+        prefix: &str,
+        constructor: impl Fn(&str) -> Result<C, String> + Send + Sync + 'static,
+    ) {
+        self.constructors.insert(
+            prefix.to_string(),
+            Box::new(move |target| Ok(Box::new(constructor(target)?))),
+        );
+    }
+
+    /// Create a check from a string in the `<prefix>#<target>` format.
+    pub fn parse(&self, s: &str) -> Result<Box<dyn Check>, String> {
+        let (prefix, target) = s
+            .split_once('#')
+            .ok_or_else(|| format!("Invalid checker: {s}"))?;
+        let constructor = self
+            .constructors
+            .get(prefix)
+            .ok_or_else(|| format!("Unknown checker type: {prefix}"))?;
+        constructor(target)
+    }
+}
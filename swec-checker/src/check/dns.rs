@@ -0,0 +1,120 @@
+use super::Check;
+use async_trait::async_trait;
+use hickory_resolver::{
+    config::{ResolverConfig, ResolverOpts},
+    error::ResolveErrorKind,
+    proto::{op::ResponseCode, rr::RecordType},
+    TokioAsyncResolver,
+};
+use std::time::{Duration, Instant};
+
+/// Checks that a name resolves, optionally to an expected value.
+#[derive(Debug, Clone)]
+pub struct Dns {
+    name: String,
+    record_type: RecordType,
+    expected: Option<String>,
+    config: ResolverConfig,
+    options: ResolverOpts,
+}
+
+impl Dns {
+    /// Create a check from `<name>#<type>`, optionally followed by `#<expected>` to compare the
+    /// first answer to. The type is one of A, AAAA, CNAME or TXT.
+    pub fn new(target: &str) -> Result<Self, String> {
+        let mut parts = target.splitn(3, '#');
+        let name = parts.next().unwrap_or_default();
+        if name.is_empty() {
+            return Err(format!("Invalid DNS check: {target}, the name is empty"));
+        }
+        let record_type = parts
+            .next()
+            .ok_or_else(|| format!("Invalid DNS check: {target}, expected <name>#<type>"))?;
+        let record_type = match record_type.to_ascii_uppercase().as_str() {
+            "A" => RecordType::A,
+            "AAAA" => RecordType::AAAA,
+            "CNAME" => RecordType::CNAME,
+            "TXT" => RecordType::TXT,
+            _ => {
+                return Err(format!(
+                    "Unsupported record type: {record_type}, expected A, AAAA, CNAME or TXT"
+                ))
+            }
+        };
+        // Read the system configuration once, so that a broken one is reported on startup.
+        let (config, options) = hickory_resolver::system_conf::read_system_conf()
+            .map_err(|e| format!("Failed to read the system DNS configuration: {e}"))?;
+        Ok(Self {
+            name: name.to_string(),
+            record_type,
+            expected: parts.next().map(ToString::to_string),
+            config,
+            options,
+        })
+    }
+
+    fn resolver(&self, timeout: Duration) -> TokioAsyncResolver {
+        let mut options = self.options.clone();
+        options.timeout = timeout;
+        options.attempts = 1;
+        // Each check must actually query the servers, not reuse the previous answer.
+        options.cache_size = 0;
+        TokioAsyncResolver::tokio(self.config.clone(), options)
+    }
+}
+
+#[async_trait]
+impl Check for Dns {
+    async fn check(&self, timeout: Duration) -> swec_core::Status {
+        let resolver = self.resolver(timeout);
+        let lookup = resolver.lookup(self.name.as_str(), self.record_type);
+        let start = Instant::now();
+        let lookup = match tokio::time::timeout(timeout, lookup).await {
+            Ok(Ok(lookup)) => lookup,
+            Ok(Err(e)) => {
+                return swec_core::Status::down(match e.kind() {
+                    ResolveErrorKind::NoRecordsFound {
+                        response_code: ResponseCode::NXDomain,
+                        ..
+                    } => format!("Error: NXDOMAIN, {} does not exist", self.name),
+                    ResolveErrorKind::NoRecordsFound {
+                        response_code: ResponseCode::NoError,
+                        ..
+                    } => format!("Error: no {} record for {}", self.record_type, self.name),
+                    ResolveErrorKind::NoRecordsFound {
+                        response_code: ResponseCode::ServFail,
+                        ..
+                    } => format!("Error: SERVFAIL while resolving {}", self.name),
+                    ResolveErrorKind::Timeout => format!("Error: timed out after {timeout:?}"),
+                    _ => format!("Error: {e}"),
+                })
+            }
+            Err(_) => {
+                return swec_core::Status::down(format!("Error: timed out after {timeout:?}"))
+            }
+        };
+        let elapsed = start.elapsed();
+        // Answers to A queries can start with the CNAME records that were followed.
+        let Some(answer) = lookup
+            .record_iter()
+            .find(|record| record.record_type() == self.record_type)
+            .and_then(|record| record.data())
+            .map(ToString::to_string)
+        else {
+            return swec_core::Status::down(format!(
+                "Error: no {} record for {}",
+                self.record_type, self.name
+            ));
+        };
+        match &self.expected {
+            Some(expected) if expected.trim_end_matches('.') != answer.trim_end_matches('.') => {
+                swec_core::Status::down(format!("Resolved to {answer}, expected {expected}"))
+            }
+            _ => swec_core::Status::up(format!("Resolved to {answer}")).with_response_time(elapsed),
+        }
+    }
+
+    fn url(&self) -> Option<String> {
+        Some(format!("dns://{}/{}", self.name, self.record_type))
+    }
+}
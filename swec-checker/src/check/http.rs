@@ -0,0 +1,250 @@
+use super::Check;
+use crate::Args;
+use async_trait::async_trait;
+use chrono::Local;
+use std::time::{Duration, Instant};
+
+/// Checks that a URL responds with a success status, and optionally with the expected content.
+#[derive(Debug, Clone)]
+pub struct Http {
+    url: reqwest::Url,
+    request: HttpRequest,
+    expectation: HttpExpectation,
+}
+
+impl Http {
+    /// Create a check of the given URL, which must be an HTTP or HTTPS one.
+    pub fn new(
+        url: &str,
+        request: HttpRequest,
+        expectation: HttpExpectation,
+    ) -> Result<Self, String> {
+        let url: reqwest::Url = url.parse().map_err(|e| format!("Invalid URL: {e}"))?;
+        if !["http", "https"].contains(&url.scheme()) {
+            return Err(format!("Invalid scheme: {}", url.scheme()));
+        }
+        Ok(Self {
+            url,
+            request,
+            expectation,
+        })
+    }
+}
+
+#[async_trait]
+impl Check for Http {
+    async fn check(&self, timeout: Duration) -> swec_core::Status {
+        let mut client = reqwest::Client::builder().timeout(timeout);
+        if self.expectation.expects_redirect() {
+            // Otherwise the redirect would be followed and its target's status checked instead.
+            client = client.redirect(reqwest::redirect::Policy::none());
+        }
+        let client = client.build().expect("Failed to create HTTP client");
+        let start = Instant::now();
+        let response = self.request.build(&client, self.url.clone()).send().await;
+        let elapsed = start.elapsed();
+        let success =
+            swec_core::Status::up(format!("Success (measured in {} ms)", elapsed.as_millis()))
+                .with_response_time(elapsed);
+        match response {
+            Ok(response) if !self.expectation.accepts_status(response.status()) => {
+                swec_core::Status::down(self.expectation.status_mismatch(response.status()))
+            }
+            Ok(_) if !self.expectation.checks_body() => success,
+            // Only read the body when it is checked, since it may be large.
+            Ok(response) => match response.text().await {
+                Ok(body) => match self.expectation.mismatch(&body) {
+                    Some(mismatch) => {
+                        swec_core::Status::down(format!("Content mismatch: {mismatch}"))
+                    }
+                    None => success,
+                },
+                Err(e) => swec_core::Status::down(format!("Error reading body: {e}")),
+            },
+            Err(e) => swec_core::Status::down(TlsFailure::of(&e).map_or_else(
+                || format!("Error: {e}"),
+                |failure| format!("TLS error: {failure}: {e}"),
+            )),
+        }
+    }
+
+    fn url(&self) -> Option<String> {
+        Some(self.url.to_string())
+    }
+}
+
+/// Why the certificate of a server was rejected, for the failures that are worth telling apart
+/// when triaging.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TlsFailure {
+    /// The certificate is not valid for the host name or IP address of the URL
+    HostnameMismatch,
+    /// The certificate, or one in its chain, has expired
+    Expired,
+    /// The certificate does not chain up to a trusted root, e.g. because it is self-signed or an
+    /// intermediate certificate is missing
+    UntrustedChain,
+}
+
+impl TlsFailure {
+    /// Find out why the certificate was rejected, if the error comes from its verification.
+    /// The TLS backend only reports this in the messages of the underlying errors, so this looks
+    /// for the known messages along the chain of sources.
+    fn of(error: &reqwest::Error) -> Option<Self> {
+        let mut source = std::error::Error::source(error);
+        let mut messages = String::new();
+        while let Some(e) = source {
+            messages.push_str(&e.to_string().to_lowercase());
+            messages.push('\n');
+            source = e.source();
+        }
+        if messages.contains("hostname mismatch")
+            || messages.contains("ip address mismatch")
+            || messages.contains("not valid for name")
+        {
+            Some(Self::HostnameMismatch)
+        } else if messages.contains("certificate has expired")
+            || messages.contains("certificate expired")
+        {
+            Some(Self::Expired)
+        } else if messages.contains("certificate verify failed")
+            || messages.contains("unknown issuer")
+            || messages.contains("self-signed certificate")
+            || messages.contains("self signed certificate")
+        {
+            Some(Self::UntrustedChain)
+        } else {
+            None
+        }
+    }
+}
+
+impl std::fmt::Display for TlsFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::HostnameMismatch => "certificate is not valid for this host",
+            Self::Expired => "certificate has expired",
+            Self::UntrustedChain => "certificate chain is not trusted",
+        })
+    }
+}
+
+/// What responses to the HTTP checker must look like for the service to be up.
+#[derive(Debug, Clone, Default)]
+pub struct HttpExpectation {
+    /// The accepted status codes, or empty to accept any success status
+    statuses: Vec<reqwest::StatusCode>,
+    /// Content the body must have, to detect pages that render an error with a success status
+    substring: Option<String>,
+    regex: Option<regex::Regex>,
+}
+
+impl HttpExpectation {
+    /// Get the expected responses from the command line arguments.
+    pub fn from_args(args: &Args) -> Result<Self, String> {
+        Ok(Self {
+            statuses: args
+                .expect_status
+                .iter()
+                .map(|&code| {
+                    reqwest::StatusCode::from_u16(code)
+                        .map_err(|_| format!("Invalid status code: {code}"))
+                })
+                .collect::<Result<_, _>>()?,
+            substring: args.expect_body.clone(),
+            regex: args
+                .expect_regex
+                .as_deref()
+                .map(regex::Regex::new)
+                .transpose()
+                .map_err(|e| format!("Invalid regex: {e}"))?,
+        })
+    }
+
+    fn accepts_status(&self, status: reqwest::StatusCode) -> bool {
+        if self.statuses.is_empty() {
+            status.is_success()
+        } else {
+            self.statuses.contains(&status)
+        }
+    }
+
+    fn expects_redirect(&self) -> bool {
+        self.statuses
+            .iter()
+            .any(reqwest::StatusCode::is_redirection)
+    }
+
+    fn status_mismatch(&self, status: reqwest::StatusCode) -> String {
+        if self.statuses.is_empty() {
+            return format!("HTTP error: {status}");
+        }
+        let expected = self
+            .statuses
+            .iter()
+            .map(|status| status.as_str())
+            .collect::<Vec<_>>()
+            .join(" or ");
+        format!("HTTP error: expected {expected}, got {status}")
+    }
+
+    const fn checks_body(&self) -> bool {
+        self.substring.is_some() || self.regex.is_some()
+    }
+
+    /// Describe what the body lacks, if anything.
+    fn mismatch(&self, body: &str) -> Option<String> {
+        if let Some(substring) = self
+            .substring
+            .as_ref()
+            .filter(|s| !body.contains(s.as_str()))
+        {
+            return Some(format!("body does not contain {substring:?}"));
+        }
+        self.regex
+            .as_ref()
+            .filter(|regex| !regex.is_match(body))
+            .map(|regex| format!("body does not match /{regex}/"))
+    }
+}
+
+/// How the HTTP checker queries its URL.
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    method: reqwest::Method,
+    /// The request body, in which `{{timestamp}}` is replaced by the time of each check
+    body: Option<String>,
+    content_type: Option<String>,
+}
+
+impl HttpRequest {
+    /// Get the request options from the command line arguments.
+    /// A body starting with `@` is read from the file at the following path, and `{{name}}` in
+    /// the body is replaced by the checker's name.
+    pub fn from_args(args: &Args) -> Result<Self, String> {
+        let body = match args.body.as_deref() {
+            Some(body) => Some(match body.strip_prefix('@') {
+                Some(path) => std::fs::read_to_string(path)
+                    .map_err(|e| format!("Failed to read body from {path}: {e}"))?,
+                None => body.to_string(),
+            }),
+            None => None,
+        };
+        Ok(Self {
+            method: args.method.clone(),
+            body: body.map(|body| body.replace("{{name}}", &args.name)),
+            content_type: args.content_type.clone(),
+        })
+    }
+
+    fn build(&self, client: &reqwest::Client, url: reqwest::Url) -> reqwest::RequestBuilder {
+        let mut request = client.request(self.method.clone(), url);
+        if let Some(content_type) = &self.content_type {
+            request = request.header(reqwest::header::CONTENT_TYPE, content_type);
+        }
+        if let Some(body) = &self.body {
+            request = request.body(body.replace("{{timestamp}}", &Local::now().to_rfc3339()));
+        }
+        request
+    }
+}
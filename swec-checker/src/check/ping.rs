@@ -0,0 +1,84 @@
+use super::Check;
+use async_trait::async_trait;
+use std::io::ErrorKind;
+use std::net::IpAddr;
+use std::time::Duration;
+use surge_ping::{Client, Config, PingIdentifier, PingSequence, SurgeError, ICMP};
+
+/// Checks that a host answers ICMP echo requests, for hosts that don't expose any TCP service.
+#[derive(Debug, Clone)]
+pub struct Ping {
+    host: String,
+}
+
+impl Ping {
+    pub fn new(host: &str) -> Result<Self, String> {
+        if host.is_empty() {
+            return Err("Invalid host: the host is empty".to_string());
+        }
+        Ok(Self {
+            host: host.to_string(),
+        })
+    }
+
+    async fn resolve(&self) -> Result<IpAddr, String> {
+        if let Ok(ip) = self.host.parse() {
+            return Ok(ip);
+        }
+        tokio::net::lookup_host((self.host.as_str(), 0))
+            .await
+            .map_err(|e| format!("Error: failed to resolve {}: {e}", self.host))?
+            .next()
+            .map(|address| address.ip())
+            .ok_or_else(|| format!("Error: {} does not resolve to any address", self.host))
+    }
+}
+
+#[async_trait]
+impl Check for Ping {
+    async fn check(&self, timeout: Duration) -> swec_core::Status {
+        let ip = match self.resolve().await {
+            Ok(ip) => ip,
+            Err(message) => return swec_core::Status::down(message),
+        };
+        let config = match ip {
+            IpAddr::V4(_) => Config::default(),
+            IpAddr::V6(_) => Config::builder().kind(ICMP::V6).build(),
+        };
+        let client = match Client::new(&config) {
+            Ok(client) => client,
+            Err(e) if e.kind() == ErrorKind::PermissionDenied => {
+                return swec_core::Status::down(
+                    "Error: not allowed to open an ICMP socket, run with CAP_NET_RAW or as root",
+                );
+            }
+            Err(e) => return swec_core::Status::down(format!("Error: failed to open socket: {e}")),
+        };
+        // The identifier only needs to tell our replies apart from those of other processes.
+        let mut pinger = client
+            .pinger(ip, PingIdentifier(std::process::id() as u16))
+            .await;
+        pinger.timeout(timeout);
+        match pinger.ping(PingSequence(0), &[0; 56]).await {
+            Ok((_, rtt)) => {
+                swec_core::Status::up(format!("Reply in {rtt:?}")).with_response_time(rtt)
+            }
+            Err(SurgeError::Timeout { .. }) => {
+                swec_core::Status::down(format!("Error: timed out after {timeout:?}"))
+            }
+            Err(SurgeError::IOError(e))
+                if matches!(
+                    e.kind(),
+                    ErrorKind::HostUnreachable | ErrorKind::NetworkUnreachable
+                ) =>
+            {
+                swec_core::Status::down(format!("Error: host unreachable: {e}"))
+            }
+            Err(e) => swec_core::Status::down(format!("Error: {e}")),
+        }
+    }
+
+    fn url(&self) -> Option<String> {
+        Some(format!("ping://{}", self.host))
+    }
+}
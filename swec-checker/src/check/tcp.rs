@@ -0,0 +1,52 @@
+use super::Check;
+use async_trait::async_trait;
+use std::time::{Duration, Instant};
+
+/// Checks that a TCP port accepts connections, for services that don't speak HTTP.
+#[derive(Debug, Clone)]
+pub struct Tcp {
+    host: String,
+    port: u16,
+}
+
+impl Tcp {
+    /// Create a check of the given `host:port`. IPv6 addresses must be in brackets, e.g.
+    /// `[::1]:5432`.
+    pub fn new(address: &str) -> Result<Self, String> {
+        let (host, port) = address
+            .rsplit_once(':')
+            .ok_or_else(|| format!("Invalid address: {address}, expected <host>:<port>"))?;
+        if host.is_empty() {
+            return Err(format!("Invalid address: {address}, the host is empty"));
+        }
+        let port = port
+            .parse()
+            .map_err(|e| format!("Invalid port in {address}: {e}"))?;
+        Ok(Self {
+            host: host.to_string(),
+            port,
+        })
+    }
+}
+
+#[async_trait]
+impl Check for Tcp {
+    async fn check(&self, timeout: Duration) -> swec_core::Status {
+        let host = self
+            .host
+            .strip_prefix('[')
+            .and_then(|host| host.strip_suffix(']'))
+            .unwrap_or(&self.host);
+        let start = Instant::now();
+        match tokio::time::timeout(timeout, tokio::net::TcpStream::connect((host, self.port))).await
+        {
+            Ok(Ok(_)) => swec_core::Status::up("Connected").with_response_time(start.elapsed()),
+            Ok(Err(e)) => swec_core::Status::down(format!("Error: {e}")),
+            Err(_) => swec_core::Status::down(format!("Error: timed out after {timeout:?}")),
+        }
+    }
+
+    fn url(&self) -> Option<String> {
+        Some(format!("tcp://{}:{}", self.host, self.port))
+    }
+}
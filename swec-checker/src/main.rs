@@ -1,14 +1,50 @@
+use chrono::{DateTime, Local};
 use clap::Parser;
-use std::str::FromStr;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
 use swec_client::{Api, ReadApi, WriteApi};
+use swec_core::Health;
 use tracing::{debug, error, info, warn};
 
+mod check;
+use check::{Check, Dns, Http, HttpExpectation, HttpRequest, Ping, Tcp};
+
 #[tokio::main]
 async fn main() {
     tracing_subscriber::fmt::init();
     let args = Args::parse();
+    let http_request = HttpRequest::from_args(&args).unwrap_or_else(|e| {
+        error!("Invalid HTTP request options: {e}");
+        std::process::exit(1);
+    });
+    let http_expectation = HttpExpectation::from_args(&args).unwrap_or_else(|e| {
+        error!("Invalid HTTP response expectation: {e}");
+        std::process::exit(1);
+    });
+    let mut registry = check::Registry::default();
+    registry.register("http", move |url| {
+        Http::new(url, http_request.clone(), http_expectation.clone())
+    });
+    registry.register("tcp", Tcp::new);
+    registry.register("ping", Ping::new);
+    registry.register("dns", Dns::new);
+    let checker = registry.parse(&args.checker).unwrap_or_else(|e| {
+        error!("{e}");
+        std::process::exit(1);
+    });
     info!("Starting checker: {}", args.name);
-    let client = swec_client::ReadWrite::new(args.api_url.clone()).unwrap_or_else(|e| {
+    let client = swec_client::ReadWrite::new_with_config(
+        args.api_url.clone(),
+        swec_client::ClientConfig {
+            token: args.api_token.clone(),
+            retry: (args.post_retries > 0).then(|| swec_client::RetryPolicy {
+                max_retries: args.post_retries,
+                ..swec_client::RetryPolicy::default()
+            }),
+            ..swec_client::ClientConfig::default()
+        },
+    )
+    .unwrap_or_else(|e| {
         error!("Failed to create API client: {e}");
         std::process::exit(1);
     });
@@ -16,12 +52,19 @@ async fn main() {
     debug!("Checking if checker exists");
     let spec = swec_core::Spec {
         description: args.description.clone(),
-        url: match &args.checker {
-            Checker::Http { url } => Some(url.to_string()),
-        },
+        url: checker.url(),
         group: args.group.clone(),
+        enabled: true,
+        channel_capacity: None,
+        history_len: args.history_len,
+        tags: args.tags.clone(),
     };
 
+    if args.validate {
+        let valid = validate(&args, checker.as_ref(), &client, &spec).await;
+        std::process::exit(i32::from(!valid));
+    }
+
     let api_info = client.get_info().await.unwrap_or_else(|e| {
         error!("Failed to get API info: {e}");
         error!("Is this a valid SWEC API? Exiting.");
@@ -33,94 +76,302 @@ async fn main() {
         std::process::exit(1);
     }
 
-    if client.get_checker(&args.name).await.is_err() {
-        info!("Checker does not exist. Sending POST request to create it");
-        client
-            .post_checker_spec(&args.name, spec)
-            .await
-            .unwrap_or_else(|e| {
-                error!("Failed to create checker: {e}");
-                std::process::exit(1);
-            });
-    } else {
-        info!("Checker already exists. Sending PUT request to update spec just in case");
-        client
-            .put_checker_spec(&args.name, spec)
-            .await
-            .expect("Failed to update checker");
-    }
+    // Don't re-enable a checker that was disabled on the server, nor shrink a history that was
+    // made longer there.
+    let spec = match client.get_checker_spec(&args.name).await {
+        Ok(existing) => swec_core::Spec {
+            enabled: existing.enabled,
+            history_len: spec.history_len.or(existing.history_len),
+            ..spec
+        },
+        Err(_) => spec,
+    };
+    info!("Creating or updating the checker's spec");
+    client
+        .put_checker_spec(&args.name, spec)
+        .await
+        .unwrap_or_else(|e| {
+            error!("Failed to create or update checker: {e}");
+            std::process::exit(1);
+        });
 
     info!("Starting main loop");
 
+    let mut batch = Batch::new(
+        args.batch_size,
+        args.batch_interval.map(Duration::from_secs),
+        args.max_queued,
+    );
+
+    let mut changes = ChangeFilter::new(args.on_change_only, args.heartbeat);
+
+    let interval = args.interval;
     loop {
+        let iteration_started = Instant::now();
+        if is_disabled(&client, &args.name).await {
+            debug!("{} is disabled, skipping check", args.name);
+            tokio::time::sleep(interval).await;
+            continue;
+        }
         debug!("Checking {}", args.name);
-        let status = args.checker.check(args.timeout).await;
+        let started = Instant::now();
+        let mut status = checker.check(args.timeout).await;
+        if status.health == Health::Up
+            && args
+                .slow_threshold
+                .is_some_and(|ms| started.elapsed() > Duration::from_millis(ms))
+        {
+            status.health = Health::Degraded;
+        }
         debug!("Status of {}: {status}", args.name);
-        client
-            .post_checker_status(&args.name, status)
-            .await
-            .unwrap_or_else(|e| {
-                warn!("Failed to post status: {e}, ignoring.");
-            });
-        debug!("Sleeping for {} seconds", args.interval);
-        tokio::time::sleep(tokio::time::Duration::from_secs(args.interval)).await;
+        if !changes.should_post(&status) {
+            debug!("Status of {} unchanged, not posting it", args.name);
+        } else if batch.is_enabled() {
+            changes.record(&status);
+            batch.push(Local::now(), status);
+        } else if !batch.is_empty() {
+            // Statuses that failed to post are still queued: keep this one after them, so that
+            // they are all posted in order.
+            changes.record(&status);
+            batch.push(Local::now(), status);
+        } else {
+            let time = Local::now();
+            match client
+                .post_checker_status_with_retry(&args.name, status.clone())
+                .await
+            {
+                Ok(()) => changes.record(&status),
+                Err(e) => {
+                    warn!("Failed to post status: {e}, queueing it to post it later.");
+                    changes.record(&status);
+                    batch.push(time, status);
+                }
+            }
+        }
+        if batch.should_flush() {
+            batch.flush(&client, &args.name).await;
+        }
+        // Sleep for what is left of the interval, so that checks start every interval regardless
+        // of how long they take.
+        let elapsed = iteration_started.elapsed();
+        if elapsed >= interval {
+            warn!(
+                "Checking took {elapsed:?}, longer than the interval of {interval:?}, checking again now"
+            );
+        } else {
+            debug!("Sleeping for {:?}", interval - elapsed);
+            tokio::time::sleep(interval - elapsed).await;
+        }
     }
 }
 
-#[derive(Debug, Clone)]
-enum Checker {
-    Http { url: reqwest::Url },
+/// Check the configuration without changing anything on the server: validate the spec, make sure
+/// the API is reachable and writable, and check the service once. Prints a summary and returns
+/// whether everything is fine.
+async fn validate(
+    args: &Args,
+    checker: &dyn Check,
+    client: &swec_client::ReadWrite,
+    spec: &swec_core::Spec,
+) -> bool {
+    let mut valid = true;
+
+    match spec.validate() {
+        Ok(()) => println!("Spec: valid ({spec})"),
+        Err(e) => {
+            println!("Spec: invalid: {e}");
+            valid = false;
+        }
+    }
+
+    match client.get_info().await {
+        Ok(info) if info.writable => println!("API: reachable and writable at {}", args.api_url),
+        Ok(_) => {
+            println!("API: reachable at {}, but not writable", args.api_url);
+            valid = false;
+        }
+        Err(e) => {
+            println!("API: unreachable at {}: {e}", args.api_url);
+            valid = false;
+        }
+    }
+
+    let started = Instant::now();
+    let status = checker.check(args.timeout).await;
+    println!(
+        "Check of {}: {status} (took {:?})",
+        args.name,
+        started.elapsed()
+    );
+    valid && status.is_up()
 }
 
-impl Checker {
-    async fn check(&self, timeout: u64) -> swec_core::Status {
-        match self {
-            Self::Http { url } => {
-                let client = reqwest::Client::builder()
-                    .timeout(std::time::Duration::from_secs(timeout))
-                    .build()
-                    .expect("Failed to create HTTP client");
-                match client.get(url.clone()).send().await {
-                    Ok(response) => {
-                        if response.status().is_success() {
-                            swec_core::Status {
-                                is_up: true,
-                                message: "Success".to_string(),
-                            }
-                        } else {
-                            swec_core::Status {
-                                is_up: false,
-                                message: format!("HTTP error: {}", response.status()),
-                            }
-                        }
-                    }
-                    Err(e) => swec_core::Status {
-                        is_up: false,
-                        message: format!("Error: {e}"),
-                    },
-                }
-            }
+/// Check whether the checker was disabled on the server.
+/// If the spec can't be fetched, assume it is still enabled so that statuses keep being posted.
+async fn is_disabled(client: &swec_client::ReadWrite, name: &str) -> bool {
+    match client.get_checker_spec(name).await {
+        Ok(spec) => !spec.enabled,
+        Err(e) => {
+            warn!("Failed to get spec: {e}, assuming the checker is enabled.");
+            false
         }
     }
 }
 
-/// Create a `Checker` from a string.
-/// The string should be in the format `http#<url>`.
-impl FromStr for Checker {
-    type Err = String;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let parts: Vec<&str> = s.splitn(2, '#').collect();
-        match parts.as_slice() {
-            ["http", url] => {
-                let url: reqwest::Url = url.parse().map_err(|e| format!("Invalid URL: {e}"))?;
-                if !["http", "https"].contains(&url.scheme()) {
-                    return Err(format!("Invalid scheme: {}", url.scheme()));
+/// Decides which statuses are posted when only changes should be: a status is posted if its
+/// state (up, degraded or down) differs from the last posted one, or if nothing was posted for the
+/// heartbeat interval, so that the server can tell the checker is still alive.
+struct ChangeFilter {
+    enabled: bool,
+    heartbeat: Duration,
+    last_posted: Option<(Health, Instant)>,
+}
+
+impl ChangeFilter {
+    const fn new(enabled: bool, heartbeat: Duration) -> Self {
+        Self {
+            enabled,
+            heartbeat,
+            last_posted: None,
+        }
+    }
+
+    fn should_post(&self, status: &swec_core::Status) -> bool {
+        !self.enabled
+            || self.last_posted.is_none_or(|(health, at)| {
+                health != status.health || at.elapsed() >= self.heartbeat
+            })
+    }
+
+    fn record(&mut self, status: &swec_core::Status) {
+        self.last_posted = Some((status.health, Instant::now()));
+    }
+}
+
+/// Statuses waiting to be posted together.
+/// The batch is flushed once it holds `max_len` statuses or once `max_age` has elapsed since the
+/// last flush, whichever comes first. Statuses are kept if a flush fails, and retried on the next
+/// one.
+///
+/// Without batching, this holds the statuses that failed to post, e.g. because the server was
+/// unreachable, which are flushed on every iteration until they get through. Either way, at most
+/// `max_queued` statuses are kept, dropping the oldest ones.
+struct Batch {
+    statuses: VecDeque<(DateTime<Local>, swec_core::Status)>,
+    max_len: Option<usize>,
+    max_age: Option<Duration>,
+    max_queued: usize,
+    /// Number of statuses dropped since the last successful flush
+    dropped: usize,
+    last_flush: Instant,
+}
+
+impl Batch {
+    fn new(max_len: Option<usize>, max_age: Option<Duration>, max_queued: usize) -> Self {
+        Self {
+            statuses: VecDeque::new(),
+            max_len,
+            max_age,
+            max_queued,
+            dropped: 0,
+            last_flush: Instant::now(),
+        }
+    }
+
+    /// Whether statuses should be batched at all, or posted as soon as they are checked.
+    const fn is_enabled(&self) -> bool {
+        self.max_len.is_some() || self.max_age.is_some()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.statuses.is_empty()
+    }
+
+    fn push(&mut self, time: DateTime<Local>, status: swec_core::Status) {
+        if self.statuses.len() >= self.max_queued {
+            self.statuses.pop_front();
+            self.dropped += 1;
+        }
+        self.statuses.push_back((time, status));
+    }
+
+    fn should_flush(&self) -> bool {
+        !self.statuses.is_empty()
+            && (self.max_len.is_some_and(|n| self.statuses.len() >= n)
+                || self
+                    .max_age
+                    .is_some_and(|age| self.last_flush.elapsed() >= age)
+                || !self.is_enabled())
+    }
+
+    async fn flush(&mut self, client: &swec_client::ReadWrite, name: &str) {
+        self.last_flush = Instant::now();
+        // Batches are all or nothing: when the server refuses some statuses as out of order, drop
+        // those and post the others again, once.
+        for _ in 0..2 {
+            debug!("Posting a batch of {} statuses", self.statuses.len());
+            match client
+                .post_checker_statuses(name, self.statuses.iter().cloned().collect())
+                .await
+            {
+                Ok(()) => {
+                    self.statuses.clear();
+                    if self.dropped > 0 {
+                        warn!(
+                            "Dropped the {} oldest statuses while they couldn't be posted, as more than {} were queued.",
+                            self.dropped, self.max_queued
+                        );
+                        self.dropped = 0;
+                    }
+                    return;
+                }
+                Err(swec_client::ApiError::Reqwest(e))
+                    if e.status() == Some(reqwest::StatusCode::CONFLICT) =>
+                {
+                    let dropped = self.drop_out_of_order(client, name).await;
+                    error!(
+                        "The server refused the batch as out of order: {e}, dropped {dropped} statuses older than the ones before them."
+                    );
+                    if self.statuses.is_empty() {
+                        return;
+                    }
+                }
+                Err(e) => {
+                    warn!(
+                        "Failed to post {} statuses: {e}, retrying on next flush.",
+                        self.statuses.len()
+                    );
+                    return;
                 }
-                Ok(Self::Http { url })
             }
-            _ => Err(format!("Invalid checker: {s}")),
         }
+        warn!(
+            "Failed to post {} statuses, retrying on next flush.",
+            self.statuses.len()
+        );
+    }
+
+    /// Drop the queued statuses that the server would refuse as out of order: those older than
+    /// its newest status or than a queued status before them. Retrying would fail the same way
+    /// for them, e.g. because the clock went back. Returns the number of dropped statuses.
+    async fn drop_out_of_order(&mut self, client: &swec_client::ReadWrite, name: &str) -> usize {
+        let mut newest = match client.get_checker_statuses_paged(name, 1, 0).await {
+            Ok(range) => range.statuses.last().map(|(time, _)| *time),
+            Err(e) => {
+                warn!("Failed to get the newest status: {e}, keeping the batch as it is.");
+                return 0;
+            }
+        };
+        let queued = self.statuses.len();
+        self.statuses.retain(|(time, _)| {
+            if newest.is_some_and(|newest| *time < newest) {
+                false
+            } else {
+                newest = Some(*time);
+                true
+            }
+        });
+        queued - self.statuses.len()
     }
 }
 
@@ -129,13 +380,85 @@ impl FromStr for Checker {
 struct Args {
     name: String,
     description: String,
-    checker: Checker,
+    /// What to check, as `<type>#<target>`: `http#<url>`, e.g. `http#https://example.com`,
+    /// `tcp#<host>:<port>`, e.g. `tcp#db.example.com:5432`, `ping#<host>`, which needs
+    /// CAP_NET_RAW or root on most systems, or `dns#<name>#<type>[#<expected>]`, e.g.
+    /// `dns#example.com#A#93.184.215.14`
+    checker: String,
     #[clap(short, long)]
     group: Option<String>,
-    #[clap(short, long, default_value = "5")]
-    interval: u64,
-    #[clap(short, long, default_value = "10")]
-    timeout: u64,
+    /// Tag to give the checker, e.g. prod. Can be given several times
+    #[clap(long = "tag")]
+    tags: Vec<String>,
+    /// Number of statuses the server keeps for the checker, instead of its default. Left as it
+    /// is on the server if not given
+    #[clap(long)]
+    history_len: Option<usize>,
+    /// Time between two checks, e.g. 500ms, 2s or 1m. Plain numbers are seconds
+    #[clap(short, long, default_value = "5", value_parser = parse_duration)]
+    interval: Duration,
+    /// Maximum duration of a check, in the same format as the interval
+    #[clap(short, long, default_value = "10", value_parser = parse_duration)]
+    timeout: Duration,
     #[clap(short, long, default_value = "http://localhost:8081/api/v1")]
     api_url: String,
+    /// Bearer token for the API, if the server requires one
+    #[clap(long, env = "SWEC_API_TOKEN", hide_env_values = true)]
+    api_token: Option<String>,
+    /// Number of times to retry posting a status when the server can't be reached or fails, with
+    /// backoff, before dropping it. A status may be recorded twice if a failed post actually
+    /// reached the server.
+    #[clap(long, default_value = "0")]
+    post_retries: u32,
+    /// HTTP method used by the HTTP checker
+    #[clap(long, default_value = "GET")]
+    method: reqwest::Method,
+    /// Body sent by the HTTP checker, or @<path> to read it from a file. `{{name}}` and
+    /// `{{timestamp}}` are replaced by the checker's name and the time of the check
+    #[clap(long)]
+    body: Option<String>,
+    /// Content type of the body sent by the HTTP checker
+    #[clap(long)]
+    content_type: Option<String>,
+    /// Status codes the HTTP checker accepts, e.g. 401 or 200,302, instead of any 2xx.
+    /// Redirects are not followed when a 3xx code is accepted
+    #[clap(long, value_delimiter = ',')]
+    expect_status: Vec<u16>,
+    /// Report the HTTP checker's service as down unless the response body contains this
+    #[clap(long)]
+    expect_body: Option<String>,
+    /// Report the HTTP checker's service as down unless the response body matches this regex
+    #[clap(long)]
+    expect_regex: Option<String>,
+    /// Mark services as degraded when the check takes longer than this many milliseconds
+    #[clap(long)]
+    slow_threshold: Option<u64>,
+    /// Post statuses in batches of this many statuses instead of one by one
+    #[clap(long)]
+    batch_size: Option<usize>,
+    /// Post batched statuses at least every this many seconds
+    #[clap(long)]
+    batch_interval: Option<u64>,
+    /// Maximum number of statuses kept while they can't be posted, e.g. because the server is
+    /// unreachable, beyond which the oldest ones are dropped. This also bounds batches
+    #[clap(long, default_value = "1000")]
+    max_queued: usize,
+    /// Only post statuses that differ from the previous one (up, degraded or down), plus a heartbeat
+    #[clap(long)]
+    on_change_only: bool,
+    /// With --on-change-only, post the status anyway after this long without posting, e.g. 5m
+    #[clap(long, default_value = "5m", value_parser = parse_duration)]
+    heartbeat: Duration,
+    /// Check the configuration, the API and the service once, print a summary and exit, without
+    /// changing anything on the server. Exits with a non-zero status if anything is wrong
+    #[clap(long)]
+    validate: bool,
+}
+
+/// Parse a duration such as `500ms` or `1m 30s`, or a plain number of seconds.
+fn parse_duration(s: &str) -> Result<Duration, humantime::DurationError> {
+    s.parse::<u64>().map_or_else(
+        |_| humantime::parse_duration(s),
+        |secs| Ok(Duration::from_secs(secs)),
+    )
 }
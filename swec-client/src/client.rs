@@ -1,12 +1,17 @@
 use async_trait::async_trait;
-use chrono::{DateTime, Local};
-use futures_util::StreamExt;
-use serde::Deserialize;
+use bytes::Bytes;
+use chrono::{DateTime, Local, SecondsFormat};
+use flate2::{write::GzEncoder, Compression};
+use futures_util::{stream, Stream, StreamExt};
 use std::collections::BTreeMap;
 use std::error::Error;
 use std::fmt::{self, Display, Formatter};
+use std::pin::Pin;
+use std::time::Duration;
 use swec_core::{
-    ApiInfo, ApiMessage, Checker, CheckerMessage, ListMessage, Spec, Status, VecBuffer,
+    ApiInfo, ApiMessage, BuildInfo, Checker, CheckerMessage, CreationResult, EncodedMessage,
+    GlobalMessage, GroupStatus, LatestStatus, ListMessage, Maintenance, ServerHealth, Spec, Status,
+    StatusRange, TransitionMessage, Uptime, VecBuffer,
 };
 use tokio::sync::mpsc::Sender;
 use tokio::task::JoinHandle;
@@ -20,6 +25,8 @@ use swec_client_derive::api_query;
 pub struct ReadOnly {
     base_url: String,
     ws_base_url: String,
+    token: Option<String>,
+    retry: Option<RetryPolicy>,
     client: reqwest::Client,
 }
 
@@ -27,11 +34,13 @@ impl Api for ReadOnly {}
 impl ReadApi for ReadOnly {}
 
 impl ApiPrivate for ReadOnly {
-    fn new_with_urls(base_url: String, ws_base_url: String) -> Self {
+    fn new_with_urls(base_url: String, ws_base_url: String, config: ClientConfig) -> Self {
         Self {
             base_url,
             ws_base_url,
-            client: reqwest::Client::new(),
+            client: http_client(&config),
+            token: config.token,
+            retry: config.retry,
         }
     }
     fn base_url(&self) -> &str {
@@ -40,15 +49,23 @@ impl ApiPrivate for ReadOnly {
     fn ws_base_url(&self) -> &str {
         &self.ws_base_url
     }
+    fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
     fn client(&self) -> &reqwest::Client {
         &self.client
     }
+    fn retry(&self) -> Option<&RetryPolicy> {
+        self.retry.as_ref()
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct ReadWrite {
     base_url: String,
     ws_base_url: String,
+    token: Option<String>,
+    retry: Option<RetryPolicy>,
     client: reqwest::Client,
 }
 
@@ -57,11 +74,13 @@ impl ReadApi for ReadWrite {}
 impl WriteApi for ReadWrite {}
 
 impl ApiPrivate for ReadWrite {
-    fn new_with_urls(base_url: String, ws_base_url: String) -> Self {
+    fn new_with_urls(base_url: String, ws_base_url: String, config: ClientConfig) -> Self {
         Self {
             base_url,
             ws_base_url,
-            client: reqwest::Client::new(),
+            client: http_client(&config),
+            token: config.token,
+            retry: config.retry,
         }
     }
     fn base_url(&self) -> &str {
@@ -70,9 +89,15 @@ impl ApiPrivate for ReadWrite {
     fn ws_base_url(&self) -> &str {
         &self.ws_base_url
     }
+    fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
     fn client(&self) -> &reqwest::Client {
         &self.client
     }
+    fn retry(&self) -> Option<&RetryPolicy> {
+        self.retry.as_ref()
+    }
 }
 
 pub trait Api: ApiPrivate {
@@ -81,6 +106,32 @@ pub trait Api: ApiPrivate {
     /// # Errors
     /// Returns `UrlFormatError` if the base URL is not a valid URL (i.e. does not start with `http://` or `https://`).
     fn new(base_url: String) -> Result<Self, UrlFormatError>
+    where
+        Self: Sized,
+    {
+        Self::new_with_token(base_url, None)
+    }
+
+    /// Create a new client that authenticates its requests with the given bearer token, if any.
+    /// # Errors
+    /// Returns `UrlFormatError` if the base URL is not a valid URL (i.e. does not start with `http://` or `https://`).
+    fn new_with_token(base_url: String, token: Option<String>) -> Result<Self, UrlFormatError>
+    where
+        Self: Sized,
+    {
+        Self::new_with_config(
+            base_url,
+            ClientConfig {
+                token,
+                ..ClientConfig::default()
+            },
+        )
+    }
+
+    /// Create a new client with the given token, timeouts and retry policy.
+    /// # Errors
+    /// Returns `UrlFormatError` if the base URL is not a valid URL (i.e. does not start with `http://` or `https://`).
+    fn new_with_config(base_url: String, config: ClientConfig) -> Result<Self, UrlFormatError>
     where
         Self: Sized,
     {
@@ -89,19 +140,117 @@ pub trait Api: ApiPrivate {
         }
         let base_url: String = base_url.trim_end_matches('/').to_string();
         let ws_base_url = base_url.replacen("http", "ws", 1);
-        Ok(Self::new_with_urls(base_url, ws_base_url))
+        Ok(Self::new_with_urls(base_url, ws_base_url, config))
+    }
+}
+
+fn http_client(config: &ClientConfig) -> reqwest::Client {
+    reqwest::Client::builder()
+        .connect_timeout(config.connect_timeout)
+        .timeout(config.timeout)
+        .build()
+        // Same as `reqwest::Client::new`, which only fails if the TLS backend can't be loaded.
+        .expect("Failed to create the HTTP client")
+}
+
+/// Options of a client, for `Api::new_with_config`.
+#[derive(Clone, Debug)]
+pub struct ClientConfig {
+    /// Bearer token to authenticate requests with, if any
+    pub token: Option<String>,
+    /// Longest time to wait for a connection to the server
+    pub connect_timeout: Duration,
+    /// Longest time a request may take, from connecting to reading the whole response. Requests
+    /// that stream large responses, like exports, must fit in it too.
+    pub timeout: Duration,
+    /// How to retry the queries that can safely be retried, or `None` to never retry
+    pub retry: Option<RetryPolicy>,
+}
+
+/// How to retry queries that fail because the server couldn't be reached, timed out or responded
+/// with a server error. Other errors, like 404s, are returned right away.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    /// Number of retries after the first attempt
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled after each failed one
+    pub min_backoff: Duration,
+    /// Longest delay between two retries
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            min_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Send the request built by `request`, retrying transient failures according to `retry`.
+/// Used by `api_query!`.
+pub(crate) async fn send_with_retry(
+    request: impl Fn() -> reqwest::RequestBuilder,
+    retry: Option<&RetryPolicy>,
+) -> Result<reqwest::Response, reqwest::Error> {
+    let mut retries = 0;
+    let mut backoff = retry.map(|retry| retry.min_backoff).unwrap_or_default();
+    loop {
+        let result = request()
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status);
+        match (result, retry) {
+            (Err(e), Some(retry)) if retries < retry.max_retries && is_transient(&e) => {
+                warn!("Query failed: {e}, retrying in {backoff:?}");
+                tokio::time::sleep(backoff).await;
+                retries += 1;
+                backoff = (backoff * 2).min(retry.max_backoff);
+            }
+            (result, _) => return result,
+        }
+    }
+}
+
+fn is_transient(e: &reqwest::Error) -> bool {
+    e.is_connect() || e.is_timeout() || e.status().is_some_and(|s| s.is_server_error())
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            token: None,
+            retry: None,
+            connect_timeout: Duration::from_secs(10),
+            timeout: Duration::from_secs(30),
+        }
     }
 }
 
 /// Private methods for the API.
 /// Should not be used directly; use the public methods from `Api`, `ReadApi`, and `WriteApi` instead.
 pub trait ApiPrivate {
-    fn new_with_urls(base_url: String, ws_base_url: String) -> Self
+    fn new_with_urls(base_url: String, ws_base_url: String, config: ClientConfig) -> Self
     where
         Self: Sized;
     fn base_url(&self) -> &str;
     fn ws_base_url(&self) -> &str;
+    fn token(&self) -> Option<&str>;
     fn client(&self) -> &reqwest::Client;
+    fn retry(&self) -> Option<&RetryPolicy>;
+
+    /// A read-only client for the same server, e.g. to make requests from a spawned task.
+    fn read_only(&self) -> ReadOnly {
+        ReadOnly {
+            base_url: self.base_url().to_string(),
+            ws_base_url: self.ws_base_url().to_string(),
+            token: self.token().map(ToString::to_string),
+            retry: self.retry().copied(),
+            client: self.client().clone(),
+        }
+    }
 }
 
 #[async_trait]
@@ -110,14 +259,82 @@ pub trait ReadApi: Api {
         api_query!(get, format!("{}/info", self.base_url()), true)
     }
 
+    async fn get_version(&self) -> Result<BuildInfo, ApiError> {
+        api_query!(get, format!("{}/version", self.base_url()), true)
+    }
+
+    async fn get_health(&self) -> Result<ServerHealth, ApiError> {
+        api_query!(get, format!("{}/healthz", self.base_url()), true)
+    }
+
     async fn get_checkers(&self) -> Result<BTreeMap<String, Checker<VecBuffer>>, ApiError> {
         api_query!(get, format!("{}/checkers", self.base_url()), true)
     }
 
+    /// Get the checkers with the given names in one request, or `None` for those that don't
+    /// exist.
+    async fn get_checkers_by_names(
+        &self,
+        names: &[&str],
+    ) -> Result<BTreeMap<String, Option<Checker<VecBuffer>>>, ApiError> {
+        let url = reqwest::Url::parse_with_params(
+            &format!("{}/checkers", self.base_url()),
+            [("names", names.join(","))],
+        )
+        .expect("Invalid URL used in API query");
+        api_query!(get, url.to_string(), true)
+    }
+
+    /// Get the names of the checkers with each tag.
+    async fn get_tags(&self) -> Result<BTreeMap<String, Vec<String>>, ApiError> {
+        api_query!(get, format!("{}/tags", self.base_url()), true)
+    }
+
+    /// Get the checkers with the given tag.
+    async fn get_checkers_by_tag(
+        &self,
+        tag: &str,
+    ) -> Result<BTreeMap<String, Checker<VecBuffer>>, ApiError> {
+        let url = reqwest::Url::parse_with_params(
+            &format!("{}/checkers", self.base_url()),
+            [("tag", tag)],
+        )
+        .expect("Invalid URL used in API query");
+        api_query!(get, url.to_string(), true)
+    }
+
+    /// Get the specs of the checkers whose name or description contains the query, ignoring
+    /// case.
+    async fn search_checkers(&self, query: &str) -> Result<BTreeMap<String, Spec>, ApiError> {
+        let url = reqwest::Url::parse_with_params(
+            &format!("{}/checkers/search", self.base_url()),
+            [("q", query)],
+        )
+        .expect("Invalid URL used in API query");
+        api_query!(get, url.to_string(), true)
+    }
+
+    /// Get the status of every group, rolled up from the latest statuses of its checkers.
+    async fn get_groups(&self) -> Result<BTreeMap<String, GroupStatus>, ApiError> {
+        api_query!(get, format!("{}/groups", self.base_url()), true)
+    }
+
+    async fn get_group(&self, name: &str) -> Result<GroupStatus, ApiError> {
+        api_query!(get, format!("{}/groups/{}", self.base_url(), name), true)
+    }
+
     async fn get_checker_names(&self) -> Result<Vec<String>, ApiError> {
         api_query!(get, format!("{}/checker_names", self.base_url()), true)
     }
 
+    /// Get the latest status of each checker and when its current state began, or `None` for
+    /// checkers without any status yet.
+    async fn get_latest_statuses(
+        &self,
+    ) -> Result<BTreeMap<String, Option<LatestStatus>>, ApiError> {
+        api_query!(get, format!("{}/latest_statuses", self.base_url()), true)
+    }
+
     async fn get_checker(&self, name: &str) -> Result<Checker<VecBuffer>, ApiError> {
         api_query!(get, format!("{}/checkers/{}", self.base_url(), name), true)
     }
@@ -130,15 +347,135 @@ pub trait ReadApi: Api {
         )
     }
 
+    /// Get the spec of a checker unless it still has the given `ETag`, as returned by a previous
+    /// call, in which case `None` is returned. Otherwise, the spec is returned with its `ETag`.
+    async fn get_checker_spec_if_changed(
+        &self,
+        name: &str,
+        etag: Option<&str>,
+    ) -> Result<Option<(Spec, String)>, ApiError> {
+        let request = self
+            .client()
+            .get(format!("{}/checkers/{}/spec", self.base_url(), name));
+        let request = match etag {
+            Some(etag) => request.header(reqwest::header::IF_NONE_MATCH, etag),
+            None => request,
+        };
+        let request = match self.token() {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        };
+        let response = request.send().await?.error_for_status()?;
+        if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+            return Ok(None);
+        }
+        let etag = response
+            .headers()
+            .get(reqwest::header::ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        Ok(Some((response.json().await?, etag)))
+    }
+
     async fn get_checker_statuses(
         &self,
         name: &str,
     ) -> Result<Vec<(DateTime<Local>, Status)>, ApiError> {
-        api_query!(
-            get,
-            format!("{}/checkers/{}/statuses", self.base_url(), name),
-            true
+        self.get_checker_statuses_range(name, None, None)
+            .await
+            .map(|range| range.statuses)
+    }
+
+    /// Get the statuses observed since `since` (inclusive) and until `until` (exclusive).
+    /// A missing bound means no limit on that side.
+    /// The returned `total` tells whether an empty range means the checker has no statuses yet.
+    async fn get_checker_statuses_range(
+        &self,
+        name: &str,
+        since: Option<DateTime<Local>>,
+        until: Option<DateTime<Local>>,
+    ) -> Result<StatusRange, ApiError> {
+        let params = [("since", since), ("until", until)]
+            .into_iter()
+            .filter_map(|(key, bound)| {
+                bound.map(|bound| (key, bound.to_rfc3339_opts(SecondsFormat::AutoSi, true)))
+            });
+        let url = reqwest::Url::parse_with_params(
+            &format!("{}/checkers/{}/statuses", self.base_url(), name),
+            params,
         )
+        .expect("Invalid URL used in API query");
+        api_query!(get, url.to_string(), true)
+    }
+
+    /// Get a page of at most `limit` statuses, skipping the `offset` newest ones.
+    /// The statuses of the page are sorted oldest first, and `total` is the number of statuses
+    /// to page through.
+    async fn get_checker_statuses_paged(
+        &self,
+        name: &str,
+        limit: usize,
+        offset: usize,
+    ) -> Result<StatusRange, ApiError> {
+        let url = reqwest::Url::parse_with_params(
+            &format!("{}/checkers/{}/statuses", self.base_url(), name),
+            [("limit", limit.to_string()), ("offset", offset.to_string())],
+        )
+        .expect("Invalid URL used in API query");
+        api_query!(get, url.to_string(), true)
+    }
+
+    /// Get the statuses of a checker added since the given index, the `head` of a previous
+    /// response, to poll for new statuses without getting the whole history each time.
+    async fn get_checker_statuses_since(
+        &self,
+        name: &str,
+        index: u64,
+    ) -> Result<StatusRange, ApiError> {
+        let url = reqwest::Url::parse_with_params(
+            &format!("{}/checkers/{}/statuses", self.base_url(), name),
+            [("since_index", index.to_string())],
+        )
+        .expect("Invalid URL used in API query");
+        api_query!(get, url.to_string(), true)
+    }
+
+    /// Get the history of the checker with one status per bucket of the given length, the worst
+    /// one of the bucket, timed at the start of the bucket.
+    async fn get_checker_statuses_rollup(
+        &self,
+        name: &str,
+        bucket: std::time::Duration,
+    ) -> Result<Vec<(DateTime<Local>, Status)>, ApiError> {
+        let url = reqwest::Url::parse_with_params(
+            &format!("{}/checkers/{}/statuses/rollup", self.base_url(), name),
+            [("bucket", format!("{}ms", bucket.as_millis()))],
+        )
+        .expect("Invalid URL used in API query");
+        api_query!(get, url.to_string(), true)
+    }
+
+    /// Get how long the checker was up and down since `since` and until `until`, or over its
+    /// whole history for missing bounds. The share of time up is in `Uptime::uptime`, from 0 to 1,
+    /// and `Uptime::percent`, from 0 to 100.
+    async fn get_checker_uptime(
+        &self,
+        name: &str,
+        since: Option<DateTime<Local>>,
+        until: Option<DateTime<Local>>,
+    ) -> Result<Uptime, ApiError> {
+        let params = [("since", since), ("until", until)]
+            .into_iter()
+            .filter_map(|(key, bound)| {
+                bound.map(|bound| (key, bound.to_rfc3339_opts(SecondsFormat::AutoSi, true)))
+            });
+        let url = reqwest::Url::parse_with_params(
+            &format!("{}/checkers/{}/uptime", self.base_url(), name),
+            params,
+        )
+        .expect("Invalid URL used in API query");
+        api_query!(get, url.to_string(), true)
     }
 
     async fn get_checker_status(&self, name: &str, n: u32) -> Result<Status, ApiError> {
@@ -149,48 +486,305 @@ pub trait ReadApi: Api {
         )
     }
 
+    /// Get the status that was current at the given time, along with the time it was observed
+    /// at. The server responds with a 404 if the checker has no status that old.
+    async fn get_checker_status_at(
+        &self,
+        name: &str,
+        time: DateTime<Local>,
+    ) -> Result<(DateTime<Local>, Status), ApiError> {
+        let url = reqwest::Url::parse_with_params(
+            &format!("{}/checkers/{}/statuses/at", self.base_url(), name),
+            [("time", time.to_rfc3339_opts(SecondsFormat::AutoSi, true))],
+        )
+        .expect("Invalid URL used in API query");
+        api_query!(get, url.to_string(), true)
+    }
+
+    /// Watch a checker. When the server lags and drops messages, the `CheckerMessage::Lagged` is
+    /// followed by an `Initial` with the current spec and latest status, fetched from the REST
+    /// API, so that the consumer can recover a known state. The channel is closed if the checker
+    /// no longer exists by then.
     async fn watch_checker(
         &self,
         name: &str,
         channel: Sender<CheckerMessage>,
     ) -> Result<JoinHandle<()>, WsError> {
         let url = format!("{}/checkers/{}/watch", self.ws_base_url(), name);
-        watch(url, channel).await
+        watch(url, resync_on_lag(self.read_only(), name, channel)).await
+    }
+
+    /// Like `watch_checker`, but reconnect whenever the websocket drops, e.g. because the server
+    /// restarted. The server starts each connection with a `CheckerMessage::Initial`, which tells
+    /// the consumer to resync. Only the first connection must succeed; after that the task ends
+    /// when `reconnect.max_retries` is exhausted or the channel is closed.
+    async fn watch_checker_reconnecting(
+        &self,
+        name: &str,
+        channel: Sender<CheckerMessage>,
+        reconnect: Reconnect,
+    ) -> Result<JoinHandle<()>, WsError> {
+        let url = format!("{}/checkers/{}/watch", self.ws_base_url(), name);
+        let channel = resync_on_lag(self.read_only(), name, channel);
+        watch_reconnecting(url, channel, reconnect).await
     }
 
     async fn watch_list(&self, channel: Sender<ListMessage>) -> Result<JoinHandle<()>, WsError> {
         let url = format!("{}/watch", self.ws_base_url());
         watch(url, channel).await
     }
+
+    /// Watch the list of checkers and all checkers over a single websocket.
+    async fn watch_all(&self, channel: Sender<GlobalMessage>) -> Result<JoinHandle<()>, WsError> {
+        let url = format!("{}/watch_all", self.ws_base_url());
+        watch(url, channel).await
+    }
+
+    /// Watch the transitions of all checkers, without their other events.
+    async fn watch_transitions(
+        &self,
+        channel: Sender<TransitionMessage>,
+    ) -> Result<JoinHandle<()>, WsError> {
+        let url = format!("{}/transitions", self.ws_base_url());
+        watch(url, channel).await
+    }
+
+    /// Watch the transitions of a checker, without its other events.
+    async fn watch_checker_transitions(
+        &self,
+        name: &str,
+        channel: Sender<TransitionMessage>,
+    ) -> Result<JoinHandle<()>, WsError> {
+        let url = format!("{}/checkers/{}/transitions", self.ws_base_url(), name);
+        watch(url, channel).await
+    }
+
+    /// Stream the whole history of a checker, oldest status first.
+    /// The body is parsed as it arrives, so that huge histories can be archived without holding
+    /// them in memory. The stream ends after the first request or connection error.
+    fn stream_export(
+        &self,
+        name: &str,
+    ) -> impl Stream<Item = Result<(DateTime<Local>, Status), ApiError>> + Send + 'static {
+        let request = self
+            .client()
+            .get(format!("{}/checkers/{}/export", self.base_url(), name));
+        let request = match self.token() {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        };
+        stream::unfold(
+            ExportState::Pending(Box::new(request)),
+            next_exported_status,
+        )
+    }
+}
+
+enum ExportState {
+    Pending(Box<reqwest::RequestBuilder>),
+    Streaming {
+        body: Pin<Box<dyn Stream<Item = reqwest::Result<Bytes>> + Send>>,
+        buffer: Vec<u8>,
+    },
+    Done,
+}
+
+/// Get the next status of an export, sending the request first if needed.
+async fn next_exported_status(
+    mut state: ExportState,
+) -> Option<(Result<(DateTime<Local>, Status), ApiError>, ExportState)> {
+    loop {
+        state = match state {
+            ExportState::Pending(request) => {
+                match request
+                    .send()
+                    .await
+                    .and_then(reqwest::Response::error_for_status)
+                {
+                    Ok(response) => ExportState::Streaming {
+                        body: Box::pin(response.bytes_stream()),
+                        buffer: Vec::new(),
+                    },
+                    Err(e) => return Some((Err(e.into()), ExportState::Done)),
+                }
+            }
+            ExportState::Streaming {
+                mut body,
+                mut buffer,
+            } => {
+                if let Some(end) = buffer.iter().position(|&b| b == b'\n') {
+                    let line: Vec<u8> = buffer.drain(..=end).collect();
+                    if line.trim_ascii().is_empty() {
+                        ExportState::Streaming { body, buffer }
+                    } else {
+                        let status = serde_json::from_slice(&line).map_err(ApiError::from);
+                        return Some((status, ExportState::Streaming { body, buffer }));
+                    }
+                } else {
+                    match body.next().await {
+                        Some(Ok(chunk)) => {
+                            buffer.extend_from_slice(&chunk);
+                            ExportState::Streaming { body, buffer }
+                        }
+                        Some(Err(e)) => return Some((Err(e.into()), ExportState::Done)),
+                        // The last line may not end with a newline.
+                        None if buffer.trim_ascii().is_empty() => return None,
+                        None => {
+                            let status = serde_json::from_slice(&buffer).map_err(ApiError::from);
+                            return Some((status, ExportState::Done));
+                        }
+                    }
+                }
+            }
+            ExportState::Done => return None,
+        };
+    }
+}
+
+/// How to reconnect a watch whose websocket dropped.
+#[derive(Clone, Copy, Debug)]
+pub struct Reconnect {
+    /// Delay before the first retry, doubled after each failed one
+    pub min_backoff: Duration,
+    /// Longest delay between two retries
+    pub max_backoff: Duration,
+    /// Number of consecutive failed retries after which to give up, or `None` to retry forever
+    pub max_retries: Option<u32>,
+}
+
+impl Default for Reconnect {
+    fn default() -> Self {
+        Self {
+            min_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            max_retries: None,
+        }
+    }
+}
+
+type WsReceiver = futures_util::stream::SplitStream<
+    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>,
+>;
+
+async fn connect(url: &str) -> Result<WsReceiver, WsError> {
+    let (ws_stream, _) = connect_async(url).await?;
+    let (_, ws_rx) = ws_stream.split();
+    Ok(ws_rx)
 }
 
-async fn watch<T: ApiMessage + for<'a> Deserialize<'a> + 'static>(
+async fn watch<T: ApiMessage + 'static>(
     url: String,
     channel: Sender<T>,
 ) -> Result<JoinHandle<()>, WsError> {
-    let (ws_stream, _) = connect_async(url).await?;
-    let (_, mut ws_rx) = ws_stream.split();
+    let ws_rx = connect(&url).await?;
 
     // Spawn a new task that will forward messages from the websocket to the channel
+    Ok(tokio::spawn(forward(ws_rx, channel)))
+}
+
+async fn watch_reconnecting<T: ApiMessage + 'static>(
+    url: String,
+    channel: Sender<T>,
+    reconnect: Reconnect,
+) -> Result<JoinHandle<()>, WsError> {
+    let mut ws_rx = connect(&url).await?;
+
     Ok(tokio::spawn(async move {
-        while let Some(msg) = ws_rx.next().await {
-            async fn f<T: for<'a> Deserialize<'a> + 'static>(
-                msg: Result<Message, tokio_tungstenite::tungstenite::Error>,
-                channel: &Sender<T>,
-            ) -> Result<(), Box<dyn Error>> {
-                let msg = msg?;
-                let msg_text = msg.to_text()?;
-                let status = serde_json::from_str(msg_text)?;
-                channel.send(status).await?;
-                Ok(())
-            }
+        loop {
+            forward(ws_rx, channel.clone()).await;
+            let mut backoff = reconnect.min_backoff;
+            let mut retries = 0;
+            ws_rx = loop {
+                if channel.is_closed() {
+                    return;
+                }
+                if reconnect.max_retries.is_some_and(|max| retries >= max) {
+                    warn!("Giving up reconnecting to {url} after {retries} retries");
+                    return;
+                }
+                warn!("Lost the websocket to {url}, reconnecting in {backoff:?}");
+                tokio::time::sleep(backoff).await;
+                retries += 1;
+                match connect(&url).await {
+                    Ok(ws_rx) => break ws_rx,
+                    Err(e) => warn!("Failed to reconnect to {url}: {e:?}"),
+                }
+                backoff = (backoff * 2).min(reconnect.max_backoff);
+            };
+        }
+    }))
+}
 
-            if let Err(e) = f(msg, &channel).await {
-                // TODO: What are the possible errors here? Should we exit the task for some of them?
-                warn!("Error reading from websocket: {e}, ignoring");
+/// Return a channel whose messages are relayed to `channel`, with a fresh
+/// `CheckerMessage::Initial` sent after each `CheckerMessage::Lagged`.
+fn resync_on_lag(
+    client: ReadOnly,
+    name: &str,
+    channel: Sender<CheckerMessage>,
+) -> Sender<CheckerMessage> {
+    let (tx, mut rx) = tokio::sync::mpsc::channel(channel.max_capacity());
+    let name = name.to_string();
+    tokio::spawn(async move {
+        while let Some(msg) = rx.recv().await {
+            let lagged = matches!(msg, CheckerMessage::Lagged { .. });
+            if channel.send(msg).await.is_err() {
+                return;
+            }
+            if !lagged {
+                continue;
+            }
+            let initial = async {
+                let spec = client.get_checker_spec(&name).await?;
+                let latest = client.get_checker_statuses_paged(&name, 1, 0).await?;
+                Ok::<_, ApiError>(CheckerMessage::Initial {
+                    spec,
+                    latest: latest.statuses.into_iter().next_back(),
+                })
+            };
+            match initial.await {
+                Ok(initial) => {
+                    if channel.send(initial).await.is_err() {
+                        return;
+                    }
+                }
+                Err(ApiError::Reqwest(e)) if e.status() == Some(reqwest::StatusCode::NOT_FOUND) => {
+                    // The checker was deleted while we lagged, and its `CheckerDropped` may be
+                    // among the dropped messages: there is nothing left to watch.
+                    return;
+                }
+                Err(e) => warn!("Failed to resync {name} after a lag: {e}, its state is unknown"),
             }
         }
-    }))
+    });
+    tx
+}
+
+/// Forward messages from the websocket to the channel until either is closed.
+async fn forward<T: ApiMessage + 'static>(mut ws_rx: WsReceiver, channel: Sender<T>) {
+    while let Some(msg) = ws_rx.next().await {
+        async fn f<T: ApiMessage + 'static>(
+            msg: Result<Message, tokio_tungstenite::tungstenite::Error>,
+            channel: &Sender<T>,
+        ) -> Result<(), Box<dyn Error + Send + Sync>> {
+            let msg = match msg? {
+                Message::Text(text) => EncodedMessage::Text(text),
+                Message::Binary(bytes) => EncodedMessage::Binary(bytes),
+                // Control frames are handled by tungstenite.
+                _ => return Ok(()),
+            };
+            let status = T::decode(&msg)?;
+            channel.send(status).await?;
+            Ok(())
+        }
+
+        if channel.is_closed() {
+            return;
+        }
+        if let Err(e) = f(msg, &channel).await {
+            // TODO: What are the possible errors here? Should we exit the task for some of them?
+            warn!("Error reading from websocket: {e}, ignoring");
+        }
+    }
 }
 
 #[async_trait]
@@ -202,6 +796,27 @@ pub trait WriteApi: Api {
             false
         )
     }
+    /// Put a checker in maintenance until the given time, or take it out of maintenance.
+    async fn set_checker_maintenance(
+        &self,
+        name: &str,
+        until: Option<DateTime<Local>>,
+    ) -> Result<(), ApiError> {
+        api_query!(
+            retry post,
+            format!("{}/checkers/{}/maintenance", self.base_url(), name),
+            false,
+            Maintenance { until }
+        )
+    }
+    /// Remove all statuses of a checker, keeping its spec.
+    async fn clear_checker_statuses(&self, name: &str) -> Result<(), ApiError> {
+        api_query!(
+            retry delete,
+            format!("{}/checkers/{}/statuses", self.base_url(), name),
+            false
+        )
+    }
     async fn post_checker_spec(&self, name: &str, spec: Spec) -> Result<(), ApiError> {
         api_query!(
             post,
@@ -210,14 +825,37 @@ pub trait WriteApi: Api {
             spec
         )
     }
+    /// Create many checkers at once. Those that can't be created are reported in the result
+    /// instead of failing the whole request.
+    async fn post_checkers(
+        &self,
+        specs: BTreeMap<String, Spec>,
+    ) -> Result<BTreeMap<String, CreationResult>, ApiError> {
+        api_query!(post, format!("{}/checkers", self.base_url()), true, specs)
+    }
+    /// Update the spec of a checker, creating the checker if it doesn't exist.
     async fn put_checker_spec(&self, name: &str, spec: Spec) -> Result<(), ApiError> {
         api_query!(
-            put,
+            retry put,
             format!("{}/checkers/{}/spec", self.base_url(), name),
             false,
             spec
         )
     }
+    async fn enable_checker(&self, name: &str) -> Result<(), ApiError> {
+        api_query!(
+            retry post,
+            format!("{}/checkers/{}/enable", self.base_url(), name),
+            false
+        )
+    }
+    async fn disable_checker(&self, name: &str) -> Result<(), ApiError> {
+        api_query!(
+            retry post,
+            format!("{}/checkers/{}/disable", self.base_url(), name),
+            false
+        )
+    }
     async fn post_checker_status(&self, name: &str, status: Status) -> Result<(), ApiError> {
         api_query!(
             post,
@@ -226,6 +864,75 @@ pub trait WriteApi: Api {
             status
         )
     }
+    /// Post a status, retrying transient failures as configured on the client. A retried status
+    /// is added twice if a failed attempt actually reached the server, e.g. when the response
+    /// timed out, so only use this where a duplicate is better than a lost status.
+    async fn post_checker_status_with_retry(
+        &self,
+        name: &str,
+        status: Status,
+    ) -> Result<(), ApiError> {
+        api_query!(
+            retry post,
+            format!("{}/checkers/{}/statuses", self.base_url(), name),
+            false,
+            status
+        )
+    }
+    /// Post a status observed at the given time, instead of the time the server receives it.
+    async fn post_checker_status_at(
+        &self,
+        name: &str,
+        time: DateTime<Local>,
+        status: Status,
+    ) -> Result<(), ApiError> {
+        api_query!(
+            post,
+            format!("{}/checkers/{}/statuses", self.base_url(), name),
+            false,
+            (time, status)
+        )
+    }
+    /// Post multiple statuses at once, all recorded at the time the server receives them.
+    async fn post_checker_statuses_now(
+        &self,
+        name: &str,
+        statuses: Vec<Status>,
+    ) -> Result<(), ApiError> {
+        api_query!(
+            post,
+            format!("{}/checkers/{}/statuses", self.base_url(), name),
+            false,
+            statuses
+        )
+    }
+    /// Post multiple statuses at once, each with the time it was observed at.
+    async fn post_checker_statuses(
+        &self,
+        name: &str,
+        statuses: Vec<(DateTime<Local>, Status)>,
+    ) -> Result<(), ApiError> {
+        // Batches can be large, e.g. when backfilling, so they are sent gzipped.
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        serde_json::to_writer(&mut encoder, &statuses)?;
+        let body = encoder.finish().expect("Writing to a Vec can't fail");
+        let request = self
+            .client()
+            .post(format!(
+                "{}/checkers/{}/statuses/batch",
+                self.base_url(),
+                name
+            ))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(reqwest::header::CONTENT_ENCODING, "gzip")
+            .body(body);
+        let request = match self.token() {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        };
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
@@ -1,9 +1,12 @@
 use clap::{Parser, Subcommand};
+use serde::Deserialize;
 use std::error::Error;
 use std::fmt::{self, Display, Formatter};
+use std::path::PathBuf;
 use std::str::FromStr;
-use swec_client::client::{Api, ReadApi, ReadOnly, ReadWrite, WriteApi};
-use swec_core::{CheckerMessage, ListMessage, Spec, Status};
+use std::time::Duration;
+use swec_client::client::{Api, ReadApi, ReadOnly, ReadWrite, Reconnect, WriteApi};
+use swec_core::{LatestStatus, Spec, Status};
 use tokio::main;
 use tokio::sync::mpsc;
 
@@ -13,30 +16,71 @@ const DEFAULT_URL_WRITE: &str = "http://localhost:8081/api/v1";
 #[main]
 async fn main() {
     let opts: Opts = Opts::parse();
+    let config = ConfigFile::load().unwrap_or_else(|e| {
+        eprintln!("Failed to read config file: {e}");
+        std::process::exit(1);
+    });
+    // Command line arguments and environment variables take precedence over the config file.
+    let base_url = opts.base_url.or(config.base_url);
+    let token = opts.token.or(config.token);
     match opts.subcmd {
         Command::Get {
             ref checker,
             ref what,
         } => {
-            let base_url = opts.base_url.unwrap_or_else(|| {
+            let base_url = base_url.unwrap_or_else(|| {
                 eprintln!("No base URL specified. Using default: {DEFAULT_URL_READ}");
                 DEFAULT_URL_READ.to_string()
             });
 
-            handle_get(base_url, checker, what).await;
+            handle_get(base_url, token, checker, what).await;
         }
         cmd => {
-            let base_url = opts.base_url.unwrap_or_else(|| {
+            let base_url = base_url.unwrap_or_else(|| {
                 eprintln!("No base URL specified. Using default: {DEFAULT_URL_WRITE}");
                 DEFAULT_URL_WRITE.to_string()
             });
-            handle_write(base_url, cmd).await;
+            handle_write(base_url, token, cmd).await;
         }
     }
 }
 
-async fn handle_get(base_url: String, checker: &Option<String>, what: &GetWhat) {
-    let client = ReadOnly::new(base_url).expect("Failed to create API client");
+/// Settings read from `$XDG_CONFIG_HOME/swec/client.toml` (or `~/.config/swec/client.toml`).
+#[derive(Deserialize, Default, Debug)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    base_url: Option<String>,
+    token: Option<String>,
+}
+
+impl ConfigFile {
+    /// Load the config file, or return an empty config if it doesn't exist.
+    fn load() -> Result<Self, Box<dyn Error>> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => Ok(toml::from_str(&contents)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("{}: {e}", path.display()).into()),
+        }
+    }
+
+    fn path() -> Option<PathBuf> {
+        let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config_dir.join("swec").join("client.toml"))
+    }
+}
+
+async fn handle_get(
+    base_url: String,
+    token: Option<String>,
+    checker: &Option<String>,
+    what: &GetWhat,
+) {
+    let client = ReadOnly::new_with_token(base_url, token).expect("Failed to create API client");
     client.get_info().await.expect("Failed to get API info");
     match checker {
         Some(checker) => match what {
@@ -58,9 +102,23 @@ async fn handle_get(base_url: String, checker: &Option<String>, what: &GetWhat)
                         .expect("Failed to get checker statuses")
                 );
             }
+            GetWhat::Metrics => {
+                let statuses = client
+                    .get_latest_statuses()
+                    .await
+                    .expect("Failed to get latest statuses")
+                    .into_iter()
+                    .filter(|(name, _)| name == checker);
+                print!("{}", to_prometheus(statuses));
+            }
             GetWhat::Watch => {
                 let (tx, mut rx) = mpsc::channel(32);
-                println!("{:?}", client.watch_checker(checker, tx).await);
+                println!(
+                    "{:?}",
+                    client
+                        .watch_checker_reconnecting(checker, tx, Reconnect::default())
+                        .await
+                );
                 while let Some(status) = rx.recv().await {
                     println!("{status}");
                 }
@@ -85,6 +143,13 @@ async fn handle_get(base_url: String, checker: &Option<String>, what: &GetWhat)
                         .collect::<Vec<_>>()
                 );
             }
+            GetWhat::Metrics => {
+                let statuses = client
+                    .get_latest_statuses()
+                    .await
+                    .expect("Failed to get latest statuses");
+                print!("{}", to_prometheus(statuses));
+            }
             GetWhat::Watch => {
                 watch_multiple(client).await;
             }
@@ -92,8 +157,40 @@ async fn handle_get(base_url: String, checker: &Option<String>, what: &GetWhat)
     }
 }
 
-async fn handle_write(base_url: String, cmd: Command) {
-    let client = ReadWrite::new(base_url).expect("Failed to create API client");
+/// Format the latest statuses of checkers in the Prometheus text exposition format.
+/// Checkers without any status are left out.
+fn to_prometheus(statuses: impl IntoIterator<Item = (String, Option<LatestStatus>)>) -> String {
+    let statuses: Vec<_> = statuses
+        .into_iter()
+        .filter_map(|(name, status)| status.map(|s| (escape_label_value(&name), s)))
+        .collect();
+    let mut out = String::new();
+    out.push_str("# HELP swec_checker_up Whether the latest status of the checker is up.\n");
+    out.push_str("# TYPE swec_checker_up gauge\n");
+    for (name, LatestStatus { status, .. }) in &statuses {
+        let up = u8::from(status.is_up());
+        out.push_str(&format!("swec_checker_up{{name=\"{name}\"}} {up}\n"));
+    }
+    out.push_str("# HELP swec_checker_last_status_timestamp_seconds Time of the latest status of the checker.\n");
+    out.push_str("# TYPE swec_checker_last_status_timestamp_seconds gauge\n");
+    for (name, LatestStatus { time, .. }) in &statuses {
+        out.push_str(&format!(
+            "swec_checker_last_status_timestamp_seconds{{name=\"{name}\"}} {}\n",
+            time.timestamp()
+        ));
+    }
+    out
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+async fn handle_write(base_url: String, token: Option<String>, cmd: Command) {
+    let client = ReadWrite::new_with_token(base_url, token).expect("Failed to create API client");
     let api_info = client.get_info().await.unwrap_or_else(|e| {
         eprintln!("Failed to get API info: {e}");
         std::process::exit(1);
@@ -117,57 +214,39 @@ async fn handle_write(base_url: String, cmd: Command) {
         Command::Put { checker, spec } => {
             println!("{:?}", client.put_checker_spec(&checker, spec).await);
         }
+        Command::Enable { checker } => {
+            println!("{:?}", client.enable_checker(&checker).await);
+        }
+        Command::Disable { checker } => {
+            println!("{:?}", client.disable_checker(&checker).await);
+        }
         Command::Get { .. } => unreachable!(), // already handled above
     }
 }
 
+/// Watch all checkers, reconnecting with exponential backoff whenever the websocket drops, e.g.
+/// because the server restarted. Each connection starts with the state of every checker, so
+/// nothing needs to be re-subscribed.
 async fn watch_multiple(client: ReadOnly) {
-    let (list_tx, mut list_rx) = mpsc::channel(32);
-    println!("{:?}", client.watch_list(list_tx).await);
-    let (checkers_tx, mut checkers_rx) = mpsc::channel(32);
-
-    tokio::spawn(async move {
-        async fn add_checker(
-            checker_name: String,
-            checkers_tx: mpsc::Sender<(String, CheckerMessage)>,
-            client: ReadOnly,
-        ) {
-            let (mapper_tx, mut mapper_rx) = mpsc::channel(32);
-            let checker_name_cloned = checker_name.clone();
-            tokio::spawn(async move {
-                while let Some(v) = mapper_rx.recv().await {
-                    checkers_tx
-                        .send((checker_name_cloned.clone(), v))
-                        .await
-                        .expect("Failed to send (checker, status) after mapping.");
-                }
-            });
-            println!(
-                "{checker_name}: {:?}",
-                client.watch_checker(&checker_name, mapper_tx).await
-            )
-        }
-
-        while let Some(v) = list_rx.recv().await {
-            match v {
-                ListMessage::Initial(checker_names) => {
-                    for checker_name in checker_names {
-                        add_checker(checker_name, checkers_tx.clone(), client.clone()).await;
-                    }
-                }
-                ListMessage::Insert(checker_name) => {
-                    add_checker(checker_name, checkers_tx.clone(), client.clone()).await;
-                }
-                ListMessage::Remove(_) | ListMessage::InsertReplace(_) => {}
-                ListMessage::Lagged(count) => {
-                    println!("The server lagged behind by {count} messages, we may not have the full list of checkers anymore.");
+    const MIN_BACKOFF: Duration = Duration::from_secs(1);
+    const MAX_BACKOFF: Duration = Duration::from_secs(60);
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        let (tx, mut rx) = mpsc::channel(32);
+        match client.watch_all(tx).await {
+            Ok(_) => {
+                backoff = MIN_BACKOFF;
+                while let Some(msg) = rx.recv().await {
+                    println!("{msg}");
                 }
+                eprintln!("Connection to the server lost, reconnecting in {backoff:?}");
+            }
+            Err(e) => {
+                eprintln!("Failed to connect to the server: {e:?}, retrying in {backoff:?}");
             }
         }
-    });
-    while let Some(v) = checkers_rx.recv().await {
-        let (checker, status) = v;
-        println!("{checker}: {status}");
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
     }
 }
 
@@ -176,9 +255,13 @@ async fn watch_multiple(client: ReadOnly) {
 struct Opts {
     /// The base URL of the API. If not specified, we will use either https://localhost:8080/api/v1
     /// (if reading) or https://localhost:8081/api/v1 (if writing)
-    #[clap(long)]
+    #[clap(long, env = "SWEC_BASE_URL")]
     base_url: Option<String>,
 
+    /// The token to authenticate with
+    #[clap(long, env = "SWEC_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+
     #[clap(subcommand)]
     subcmd: Command,
 }
@@ -202,7 +285,7 @@ enum Command {
         #[clap(subcommand)]
         what: PostWhat,
     },
-    /// Put a checker spec to the server
+    /// Put a checker spec to the server, creating the checker if it does not exist
     Put {
         /// The checker to put to
         checker: String,
@@ -215,6 +298,16 @@ enum Command {
         /// The checker to delete
         checker: String,
     },
+    /// Resume checking a disabled checker
+    Enable {
+        /// The checker to enable
+        checker: String,
+    },
+    /// Pause checking a checker without deleting its history
+    Disable {
+        /// The checker to disable
+        checker: String,
+    },
 }
 
 #[derive(Parser, Debug, Clone)]
@@ -222,6 +315,8 @@ enum GetWhat {
     Spec,
     Statuses,
     Watch,
+    /// The latest statuses, in the Prometheus text format
+    Metrics,
 }
 
 impl FromStr for GetWhat {
@@ -232,6 +327,7 @@ impl FromStr for GetWhat {
             "spec" => Ok(Self::Spec),
             "statuses" => Ok(Self::Statuses),
             "watch" => Ok(Self::Watch),
+            "metrics" => Ok(Self::Metrics),
             _ => Err(UnknownValueError(s.to_string())),
         }
     }
@@ -4,6 +4,7 @@ use quote::quote;
 use syn::parse_macro_input;
 
 struct ApiQuery {
+    retry: bool,
     method: syn::Ident,
     url: syn::Expr,
     get_json: syn::LitBool,
@@ -12,7 +13,11 @@ struct ApiQuery {
 
 impl syn::parse::Parse for ApiQuery {
     fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-        let method: syn::Ident = input.parse()?;
+        let mut method: syn::Ident = input.parse()?;
+        let retry = method == "retry";
+        if retry {
+            method = input.parse()?;
+        }
         input.parse::<syn::Token![,]>()?;
         let url: syn::Expr = input.parse()?;
         input.parse::<syn::Token![,]>()?;
@@ -24,6 +29,7 @@ impl syn::parse::Parse for ApiQuery {
             None
         };
         Ok(Self {
+            retry,
             method,
             url,
             get_json,
@@ -34,6 +40,8 @@ impl syn::parse::Parse for ApiQuery {
 
 /// Make an API query.
 /// Arguments:
+/// - retry: Optionally, the `retry` keyword before the method, to retry transient failures as
+///   configured on the client. Only use it for idempotent queries.
 /// - method: The HTTP method to use (get, post, put, delete)
 /// - url: The URL to query
 /// - `get_json`: Whether to parse the response as JSON and return it
@@ -42,6 +50,7 @@ impl syn::parse::Parse for ApiQuery {
 pub fn api_query(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as ApiQuery);
     let method = input.method;
+    let retry = input.retry;
     let url = input.url;
     let get_json = input.get_json.value;
     let json_str = if get_json {
@@ -53,18 +62,26 @@ pub fn api_query(input: TokenStream) -> TokenStream {
         Ok(())
         }
     };
-    let data_str = input
-        .data
-        .map_or_else(|| quote! {}, |data| quote! { .json(&#data) });
+    // The data is evaluated once, outside of the closure that builds each attempt.
+    let (data_bind, data_str) = input.data.map_or_else(
+        || (quote! {}, quote! {}),
+        |data| (quote! { let data = #data; }, quote! { .json(&data) }),
+    );
     let gen = quote! {
         {
             let url = #url;
             let url = url.parse::<reqwest::Url>().expect("Invalid URL used in API query");
-            let response = self.client().#method(url)
-                #data_str
-                .send()
-                .await?
-                .error_for_status()?;
+            #data_bind
+            let request = || {
+                let request = self.client().#method(url.clone());
+                let request = match self.token() {
+                    Some(token) => request.bearer_auth(token),
+                    None => request,
+                };
+                request #data_str
+            };
+            let retry = if #retry { self.retry() } else { None };
+            let response = crate::client::send_with_retry(request, retry).await?;
             #json_str
         }
     };
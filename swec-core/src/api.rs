@@ -1,7 +1,8 @@
 use crate::{checker, Spec};
 use chrono::{DateTime, Local};
-use serde::{Deserialize, Serialize};
-use std::collections::BTreeSet;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
 use std::fmt::{self, Display, Formatter};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -10,58 +11,256 @@ pub struct Info {
     pub swec_version: String,
 }
 
+/// The build of the server, from the `/version` endpoint.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BuildInfo {
+    pub swec_version: String,
+    /// Commit the server was built from, if it was given to the build
+    pub git_sha: Option<String>,
+    /// When the server was built, if it was given to the build
+    pub built_at: Option<String>,
+}
+
+/// The health of the server itself, from the `/healthz` endpoint.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ServerHealth {
+    /// Time since the server started, in seconds
+    pub uptime_secs: u64,
+    /// Number of checkers on the server
+    pub checkers: usize,
+}
+
+/// The body of every error response of the API.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub error: ErrorDetail,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ErrorDetail {
+    /// A machine-readable error code, e.g. `checker_not_found`
+    pub code: String,
+    /// A human-readable description of the error
+    pub message: String,
+}
+
+impl Display for ErrorDetail {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.message, self.code)
+    }
+}
+
+/// The maintenance window of a checker, as set with `POST /checkers/:name/maintenance`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Maintenance {
+    /// End of the window, or `None` to end the maintenance now
+    pub until: Option<DateTime<Local>>,
+}
+
+/// What became of one of the checkers posted at once to `POST /checkers`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum CreationResult {
+    /// The checker was created
+    Created,
+    /// A checker with that name already exists, and was left as it was
+    Conflict,
+    /// The spec failed validation
+    Invalid { reason: String },
+    /// The name is outside of the prefix allowed for the token of the request
+    Forbidden,
+}
+
+/// Statuses of a checker in a time range.
+///
+/// Endpoints returning statuses distinguish three cases:
+/// - the checker does not exist: a `checker_not_found` error (404);
+/// - the checker has no statuses yet: `statuses` is empty and `total` is 0;
+/// - the checker has statuses, but none in the range: `statuses` is empty and `total` is not 0.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StatusRange {
+    /// The statuses in the range, oldest first
+    pub statuses: Vec<(DateTime<Local>, checker::Status)>,
+    /// The number of statuses in the whole history of the checker, in the range or not
+    pub total: usize,
+    /// The index the next status of the checker will have, to pass as `since_index` to get only
+    /// the statuses added since
+    #[serde(default)]
+    pub head: u64,
+}
+
+/// How long a checker was up and down in a time range.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Uptime {
+    /// Seconds the checker was up
+    pub up_secs: f64,
+    /// Seconds the checker was down
+    pub down_secs: f64,
+    /// Seconds without data: before the first status, and in gaps between statuses longer than
+    /// the server's maximum gap, e.g. while the server or the checker was down
+    pub unknown_secs: f64,
+    /// Share of the known time the checker was up, from 0 to 1, or `None` if no time is known.
+    /// Older servers called it `ratio`.
+    #[serde(alias = "ratio")]
+    pub uptime: Option<f64>,
+    /// The same share as a percentage, from 0 to 100, e.g. 99.7 for an SLA
+    #[serde(default)]
+    pub percent: Option<f64>,
+    /// Number of statuses observed in the range
+    #[serde(default)]
+    pub samples: usize,
+}
+
+/// The aggregate state of the checkers of a group.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupState {
+    /// Enough checkers are up, according to the server's rollup policy
+    Healthy,
+    /// Some checkers are up, but not enough
+    Degraded,
+    /// No checker is up
+    Down,
+    /// No checker has a status yet
+    Unknown,
+}
+
+impl Display for GroupState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Healthy => "Healthy",
+            Self::Degraded => "Degraded",
+            Self::Down => "Down",
+            Self::Unknown => "Unknown",
+        })
+    }
+}
+
+/// The aggregate status of a group, computed from the latest statuses of its checkers.
+/// Disabled checkers and checkers without statuses are listed but don't count.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GroupStatus {
+    pub state: GroupState,
+    /// Checkers of the group whose latest status is up
+    pub up: Vec<String>,
+    /// Checkers of the group whose latest status is down
+    pub down: Vec<String>,
+    /// Checkers of the group that are disabled or have no status yet
+    pub unknown: Vec<String>,
+}
+
+/// The latest status of a checker, with the time its current state began.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LatestStatus {
+    /// When the status was observed
+    pub time: DateTime<Local>,
+    pub status: checker::Status,
+    /// When the checker went into its current state (up or down), for displays such as "down for
+    /// 3h". This is only as old as the oldest status the server keeps.
+    pub since: DateTime<Local>,
+}
+
 /// A message sent by the server to notify the client of an event on a checker.
 /// # Guarantees
 /// The server guarantees that the client will receive messages for all updates of a checker,
 /// unless there is a lag (See `CheckerMessage::Lagged`).
+///
+/// # Wire format
+/// Messages are objects with a `type` field naming the variant in snake case, and the fields of
+/// the variant alongside it, e.g. `{"type":"added_status","time":"...","status":{...}}`.
+/// Variants are renamed explicitly, so that renaming them in Rust doesn't change the wire format.
+///
+/// Before this format, messages were externally tagged with the Rust variant names and
+/// positional fields, e.g. `{"AddedStatus":["...",{...}]}`. Clients must be updated along with
+/// the server.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
 pub enum CheckerMessage {
     /// The checker's initial spec and status.
-    /// This is the first message received for a checker, and contains the spec and the first
+    /// This is the first message received for a checker, and contains the spec and the latest
     /// status if it exists.
-    Initial(Spec, Option<(DateTime<Local>, checker::Status)>),
+    #[serde(rename = "initial")]
+    Initial {
+        spec: Spec,
+        latest: Option<(DateTime<Local>, checker::Status)>,
+    },
 
     /// The checker's spec was updated.
-    UpdatedSpec(checker::Spec),
+    #[serde(rename = "updated_spec")]
+    UpdatedSpec { spec: checker::Spec },
 
     /// A status was added to the checker.
-    AddedStatus(DateTime<Local>, checker::Status),
+    #[serde(rename = "added_status")]
+    AddedStatus {
+        time: DateTime<Local>,
+        status: checker::Status,
+    },
+
+    /// The checker went up or down and stayed in that state for long enough to be considered a
+    /// real change rather than a blip, according to the damping configured on the server.
+    #[serde(rename = "transition")]
+    Transition {
+        /// The time of the first status in the new state
+        since: DateTime<Local>,
+        /// Whether the checker is now up
+        is_up: bool,
+    },
+
+    /// The checker went into maintenance until the given time, or out of it if there is none.
+    /// Statuses observed during maintenance are flagged, so that being down can be ignored.
+    #[serde(rename = "maintenance_changed")]
+    MaintenanceChanged { until: Option<DateTime<Local>> },
+
+    /// All statuses of the checker were removed. Its spec is unchanged, and new statuses will
+    /// follow as usual.
+    #[serde(rename = "history_cleared")]
+    HistoryCleared,
 
     /// The checker was dropped by the server.
     /// This should be the last message received for the checker; after this, the server will
     /// either shut down or the watcher will be removed, both of which will result in the
     /// websocket being closed.
+    #[serde(rename = "checker_dropped")]
     CheckerDropped,
 
     /// The server lagged by the given number of messages which were dropped.
     /// This means the guarantee of receiving all updates for the checker is broken, and the client
     /// should consider the checker to be in an unknown state.
-    Lagged(u64),
+    #[serde(rename = "lagged")]
+    Lagged { count: u64 },
 }
 
 impl Display for CheckerMessage {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
-            Self::UpdatedSpec(spec) => write!(f, "Updated spec: {spec}"),
-            Self::AddedStatus(time, status) => {
+            Self::UpdatedSpec { spec } => write!(f, "Updated spec: {spec}"),
+            Self::AddedStatus { time, status } => {
                 write!(f, "Added status at {time}: {status}")
             }
-            Self::Initial(spec, status) => {
+            Self::Initial { spec, latest } => {
                 write!(f, "Initial spec: {spec}")?;
-                if let Some((time, status)) = status {
+                if let Some((time, status)) = latest {
                     write!(f, ", initial status at {time}: {status}")?;
                 }
                 Ok(())
             }
+            Self::Transition { since, is_up } => {
+                write!(f, "Went {} at {since}", if *is_up { "up" } else { "down" })
+            }
+            Self::MaintenanceChanged { until: Some(until) } => {
+                write!(f, "In maintenance until {until}")
+            }
+            Self::MaintenanceChanged { until: None } => write!(f, "Out of maintenance"),
+            Self::HistoryCleared => write!(f, "History cleared"),
             Self::CheckerDropped => write!(f, "Checker dropped by server"),
-            Self::Lagged(n) => write!(f, "Server lagged and dropped {n} messages"),
+            Self::Lagged { count } => write!(f, "Server lagged and dropped {count} messages"),
         }
     }
 }
 
 impl Message for CheckerMessage {
     fn new_lag(n: u64) -> Self {
-        Self::Lagged(n)
+        Self::Lagged { count: n }
     }
 }
 
@@ -69,44 +268,243 @@ impl Message for CheckerMessage {
 /// Useful for watching all checkers.
 /// # Guarantees
 /// - The server guarantees that the client will receive messages for all updates of a checker, unless there is a lag (See `GlobalMessage::Lagged`).
+///
+/// Messages have the same format as `CheckerMessage`s.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
 pub enum ListMessage {
     /// The initial list of checkers.
-    Initial(BTreeSet<String>),
+    #[serde(rename = "initial")]
+    Initial { names: BTreeSet<String> },
 
     /// A new checker was inserted.
-    Insert(String),
+    #[serde(rename = "insert")]
+    Insert { name: String },
 
     /// A checker was replaced with insert().
-    InsertReplace(String),
+    #[serde(rename = "insert_replace")]
+    InsertReplace { name: String },
 
     /// A checker was removed.
-    Remove(String),
+    #[serde(rename = "remove")]
+    Remove { name: String },
 
     /// The server lagged by the given number of messages which were dropped.
     /// This means the guarantee of receiving all updates for the checker is broken, and the client
     /// should consider the list of checkers to be in an unknown state.
-    Lagged(u64),
+    #[serde(rename = "lagged")]
+    Lagged { count: u64 },
 }
 
 impl Display for ListMessage {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
-            Self::Initial(watchers) => write!(f, "Initial watchers: {watchers:?}"),
-            Self::Lagged(n) => write!(f, "Server lagged and dropped {n} messages"),
-            Self::Insert(w) => write!(f, "Inserted watcher: {w}"),
-            Self::InsertReplace(w) => write!(f, "Inserted and replaced watcher: {w}"),
-            Self::Remove(w) => write!(f, "Removed watcher: {w}"),
+            Self::Initial { names } => write!(f, "Initial watchers: {names:?}"),
+            Self::Lagged { count } => write!(f, "Server lagged and dropped {count} messages"),
+            Self::Insert { name } => write!(f, "Inserted watcher: {name}"),
+            Self::InsertReplace { name } => write!(f, "Inserted and replaced watcher: {name}"),
+            Self::Remove { name } => write!(f, "Removed watcher: {name}"),
         }
     }
 }
 
 impl Message for ListMessage {
     fn new_lag(n: u64) -> Self {
-        Self::Lagged(n)
+        Self::Lagged { count: n }
     }
 }
 
-pub trait Message: Clone + Send + Serialize {
+/// The spec of a checker and its latest status, if any.
+pub type CheckerSummary = (Spec, Option<LatestStatus>);
+
+/// A message sent by the server to notify the client of an event on the list of checkers or on
+/// any checker, so that all checkers can be watched over a single websocket.
+/// # Guarantees
+/// - The server guarantees that the client will receive messages for all updates of the list and
+///   of every checker, unless there is a lag (See `GlobalMessage::Lagged`).
+///
+/// Messages have the same format as `CheckerMessage`s, with the wrapped message in `message`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum GlobalMessage {
+    /// The initial spec and latest status of every checker.
+    #[serde(rename = "initial")]
+    Initial {
+        checkers: BTreeMap<String, CheckerSummary>,
+    },
+
+    /// An event on the list of checkers.
+    /// This is never `ListMessage::Initial` nor `ListMessage::Lagged`.
+    #[serde(rename = "list")]
+    List { message: ListMessage },
+
+    /// An event on the named checker.
+    /// This is never `CheckerMessage::Initial`, `CheckerMessage::CheckerDropped` (a
+    /// `ListMessage::Remove` is sent instead) nor `CheckerMessage::Lagged`. A checker inserted
+    /// with `ListMessage::Insert` has no statuses yet; its spec is sent right after.
+    #[serde(rename = "checker")]
+    Checker {
+        name: String,
+        message: CheckerMessage,
+    },
+
+    /// The server lagged by the given number of messages which were dropped.
+    /// This means the guarantee of receiving all updates is broken, and the client should
+    /// consider all checkers to be in an unknown state.
+    #[serde(rename = "lagged")]
+    Lagged { count: u64 },
+}
+
+impl Display for GlobalMessage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Initial { checkers } => {
+                write!(f, "Initial checkers:")?;
+                for (name, (spec, status)) in checkers {
+                    write!(f, "\n{name}: {spec}")?;
+                    if let Some(LatestStatus {
+                        time,
+                        status,
+                        since,
+                    }) = status
+                    {
+                        write!(f, ", latest status at {time}: {status} (since {since})")?;
+                    }
+                }
+                Ok(())
+            }
+            Self::List { message } => write!(f, "{message}"),
+            Self::Checker { name, message } => write!(f, "{name}: {message}"),
+            Self::Lagged { count } => write!(f, "Server lagged and dropped {count} messages"),
+        }
+    }
+}
+
+impl Message for GlobalMessage {
+    fn new_lag(n: u64) -> Self {
+        Self::Lagged { count: n }
+    }
+}
+
+/// A message sent by the server when a checker goes up or down, for clients such as alerting
+/// bridges that only care about transitions.
+/// # Guarantees
+/// - The server guarantees that the client will receive all transitions, unless there is a lag
+///   (See `TransitionMessage::Lagged`).
+///
+/// Messages have the same format as `CheckerMessage`s.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum TransitionMessage {
+    /// The current state of the watched checkers: whether each one is up, or `None` if it has no
+    /// status yet.
+    /// This is the first message received.
+    #[serde(rename = "initial")]
+    Initial {
+        states: BTreeMap<String, Option<bool>>,
+    },
+
+    /// The named checker went up or down at `since`.
+    #[serde(rename = "transition")]
+    Transition {
+        name: String,
+        since: DateTime<Local>,
+        is_up: bool,
+    },
+
+    /// The server lagged by the given number of messages which were dropped.
+    /// This means the guarantee of receiving all transitions is broken, and the client should
+    /// consider all checkers to be in an unknown state.
+    #[serde(rename = "lagged")]
+    Lagged { count: u64 },
+}
+
+impl Display for TransitionMessage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Initial { states } => {
+                write!(f, "Initial states:")?;
+                for (name, is_up) in states {
+                    let state = match is_up {
+                        Some(true) => "up",
+                        Some(false) => "down",
+                        None => "unknown",
+                    };
+                    write!(f, "\n{name}: {state}")?;
+                }
+                Ok(())
+            }
+            Self::Transition { name, since, is_up } => {
+                write!(
+                    f,
+                    "{name} went {} at {since}",
+                    if *is_up { "up" } else { "down" }
+                )
+            }
+            Self::Lagged { count } => write!(f, "Server lagged and dropped {count} messages"),
+        }
+    }
+}
+
+impl Message for TransitionMessage {
+    fn new_lag(n: u64) -> Self {
+        Self::Lagged { count: n }
+    }
+}
+
+pub trait Message: Clone + Send + Sync + Serialize + DeserializeOwned {
     fn new_lag(n: u64) -> Self;
+
+    /// Encode the message for sending over a websocket.
+    /// # Errors
+    /// Returns an error if the message could not be serialized.
+    fn encode(&self, encoding: Encoding) -> Result<EncodedMessage, Box<dyn Error + Send + Sync>> {
+        Ok(match encoding {
+            Encoding::Json => EncodedMessage::Text(serde_json::to_string(self)?),
+            Encoding::MessagePack => EncodedMessage::Binary(rmp_serde::to_vec_named(self)?),
+        })
+    }
+
+    /// Decode a message received over a websocket. Text frames are JSON, binary frames are
+    /// MessagePack.
+    /// # Errors
+    /// Returns an error if the message could not be deserialized.
+    fn decode(msg: &EncodedMessage) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Ok(match msg {
+            EncodedMessage::Text(text) => serde_json::from_str(text)?,
+            EncodedMessage::Binary(bytes) => rmp_serde::from_slice(bytes)?,
+        })
+    }
+}
+
+/// The encoding used for websocket messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+    /// JSON in text frames. This is the default, and what browsers expect.
+    #[default]
+    Json,
+    /// MessagePack in binary frames, which is more compact for high-frequency updates.
+    /// Clients opt into it by requesting the `MESSAGEPACK_PROTOCOL` subprotocol on upgrade.
+    MessagePack,
+}
+
+impl Encoding {
+    /// The websocket subprotocol clients request to receive MessagePack messages.
+    pub const MESSAGEPACK_PROTOCOL: &'static str = "swec.msgpack";
+
+    /// Get the encoding for a negotiated websocket subprotocol, defaulting to JSON.
+    #[must_use]
+    pub fn from_protocol(protocol: Option<&str>) -> Self {
+        match protocol {
+            Some(Self::MESSAGEPACK_PROTOCOL) => Self::MessagePack,
+            _ => Self::Json,
+        }
+    }
+}
+
+/// A message as sent over a websocket, either as text or binary.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EncodedMessage {
+    Text(String),
+    Binary(Vec<u8>),
 }
@@ -90,6 +90,27 @@ pub struct Spec {
     pub url: Option<String>,
     /// A group the checker may belong to
     pub group: Option<String>,
+    /// Whether the service is currently being checked. Disabled checkers keep their history, but
+    /// checkers should not post new statuses for them.
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+    /// Number of messages the server queues for those watching the checker before the slowest
+    /// ones lag, or `None` for the server's default. Checkers that update often and have many
+    /// watchers need more. This is only read when the server creates or restores the checker.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel_capacity: Option<usize>,
+    /// Number of statuses the server keeps for the checker, or `None` for the server's default,
+    /// e.g. to keep more of checkers that post often. Lowering it drops the oldest statuses.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history_len: Option<usize>,
+    /// Free-form labels to select checkers by, e.g. `prod`. Unlike the group, a checker can have
+    /// any number of them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+const fn enabled_by_default() -> bool {
+    true
 }
 
 impl Spec {
@@ -99,13 +120,70 @@ impl Spec {
             description,
             url,
             group,
+            enabled: true,
+            channel_capacity: None,
+            history_len: None,
+            tags: Vec::new(),
+        }
+    }
+
+    /// The number of statuses kept for the checker, given the server's default. Histories can't
+    /// be empty, so this is at least 1 even if validation was skipped.
+    #[must_use]
+    pub fn history_len_or(&self, default: usize) -> usize {
+        self.history_len.unwrap_or(default).max(1)
+    }
+
+    /// Whether the spec has the given tag.
+    #[must_use]
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    /// Check that the spec is well-formed, i.e. that its URL parses, if it has one.
+    /// # Errors
+    /// Returns an `InvalidSpec` describing the first problem found.
+    pub fn validate(&self) -> Result<(), InvalidSpec> {
+        if let Some(url) = &self.url {
+            url::Url::parse(url).map_err(|e| InvalidSpec(format!("Invalid URL {url:?}: {e}")))?;
         }
+        if self.history_len == Some(0) {
+            return Err(InvalidSpec(
+                "The history length must be at least 1".to_string(),
+            ));
+        }
+        // Tags are joined with commas in the string form of specs, so they can't contain any.
+        if let Some(tag) = self
+            .tags
+            .iter()
+            .find(|tag| tag.is_empty() || tag.contains(','))
+        {
+            return Err(InvalidSpec(format!(
+                "Invalid tag {tag:?}: tags must be non-empty and can't contain commas"
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// A spec failed validation, for the given reason.
+#[derive(Debug, Clone)]
+pub struct InvalidSpec(pub String);
+
+impl Display for InvalidSpec {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
     }
 }
 
+impl std::error::Error for InvalidSpec {}
+
 impl Display for Spec {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(f, "{}", String::from(self))?;
+        if !self.enabled {
+            write!(f, " (disabled)")?;
+        }
         Ok(())
     }
 }
@@ -121,20 +199,29 @@ impl From<&Spec> for String {
             s.push('#');
             s.push_str(group);
         }
+        if !spec.tags.is_empty() {
+            if spec.group.is_none() {
+                s.push('#');
+            }
+            s.push('#');
+            s.push_str(&spec.tags.join(","));
+        }
         s
     }
 }
 
 /// Create a `Spec` from a string.
-/// The string should be in the format `<description>[@<url>][#<group>]` or `<description>[#<group>][@<url>]`.
+/// The string should be in the format `<description>[@<url>][#<group>][#<tags>]` or
+/// `<description>[#<group>][#<tags>][@<url>]`, where `<tags>` are separated by commas. The group
+/// can be left empty to only give tags, e.g. `API##prod,eu`.
 impl FromStr for Spec {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // Verify that there are only one '@' and one '#' max
-        if s.matches('@').count() > 1 || s.matches('#').count() > 1 {
+        // Verify that there are only one '@' and two '#' max
+        if s.matches('@').count() > 1 || s.matches('#').count() > 2 {
             return Err(format!(
-                "Invalid spec: {s}. Expected format: <description>[@<url>][#<group>] or <description>[#<group>][@<url>]"
+                "Invalid spec: {s}. Expected format: <description>[@<url>][#<group>][#<tags>] or <description>[#<group>][#<tags>][@<url>]"
             ));
         }
         let description = s
@@ -146,27 +233,178 @@ impl FromStr for Spec {
             .map(|x| x.1) // Get the part after the '@'
             .and_then(|s| s.split('#').next()) // And before '#'
             .map(ToString::to_string);
-        let group = s
-            .split_once('#')
-            .map(|x| x.1) // Get the part after the '#'
-            .and_then(|s| s.split('@').next()) // And before '@'
+        let mut hashes = s
+            .split('#')
+            .skip(1) // Skip the part before the first '#'
+            .map(|s| s.split('@').next().expect("Should have at least one part")); // Before '@'
+        let group = hashes
+            .next()
+            .filter(|group| !group.is_empty() || s.matches('#').count() < 2)
             .map(ToString::to_string);
-        Ok(Self::new(description.to_string(), url, group))
+        let tags = hashes
+            .next()
+            .map(|tags| tags.split(',').map(ToString::to_string).collect())
+            .unwrap_or_default();
+        Ok(Self {
+            tags,
+            ..Self::new(description.to_string(), url, group)
+        })
+    }
+}
+
+/// How well a service is doing.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Health {
+    Up,
+    /// The service responds, but not as well as it should, e.g. slower than the checker's
+    /// threshold. It still counts as up, for transitions and uptime.
+    Degraded,
+    Down,
+}
+
+impl Display for Health {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Up => "Up",
+            Self::Degraded => "Degraded",
+            Self::Down => "Down",
+        })
     }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(from = "StatusRepr", into = "StatusRepr")]
 pub struct Status {
-    /// Whether the service is up or down
-    pub is_up: bool,
+    /// Whether the service is up, degraded or down
+    pub health: Health,
     /// Human readable information about the status
     pub message: String,
+    /// How long the service took to respond, if the checker measured it
+    pub response_time: Option<std::time::Duration>,
+    /// Whether the checker was in maintenance when the status was observed, in which case being
+    /// down is expected
+    pub maintenance: bool,
+}
+
+/// The serialized form of `Status`.
+/// `is_up` and `slow` predate `health` and are still written for older clients. When `health` is
+/// missing, as in older dumps, it is derived from them: slow statuses were up ones that took
+/// longer than the checker's threshold, which are now degraded.
+#[derive(Serialize, Deserialize)]
+struct StatusRepr {
+    is_up: bool,
+    message: String,
+    #[serde(default)]
+    slow: bool,
+    #[serde(default)]
+    health: Option<Health>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    response_time: Option<std::time::Duration>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    maintenance: bool,
+}
+
+impl From<StatusRepr> for Status {
+    fn from(repr: StatusRepr) -> Self {
+        let health = repr.health.unwrap_or(match (repr.is_up, repr.slow) {
+            (true, false) => Health::Up,
+            (true, true) => Health::Degraded,
+            (false, _) => Health::Down,
+        });
+        Self {
+            health,
+            message: repr.message,
+            response_time: repr.response_time,
+            maintenance: repr.maintenance,
+        }
+    }
+}
+
+impl From<Status> for StatusRepr {
+    fn from(status: Status) -> Self {
+        Self {
+            is_up: status.is_up(),
+            slow: status.health == Health::Degraded,
+            health: Some(status.health),
+            message: status.message,
+            response_time: status.response_time,
+            maintenance: status.maintenance,
+        }
+    }
+}
+
+/// Statuses should be created with these rather than with struct literals, so that adding fields
+/// doesn't break the code creating them: new fields get a default value, which can be changed by
+/// chaining the `with_*` methods, e.g. `Status::up("OK").with_response_time(elapsed)`.
+impl Status {
+    /// Create a status of a service that is up.
+    #[must_use]
+    pub fn up(message: impl Into<String>) -> Self {
+        Self::new(true, message)
+    }
+
+    /// Create a status of a service that is degraded.
+    #[must_use]
+    pub fn degraded(message: impl Into<String>) -> Self {
+        Self::new(true, message).with_health(Health::Degraded)
+    }
+
+    /// Create a status of a service that is down.
+    #[must_use]
+    pub fn down(message: impl Into<String>) -> Self {
+        Self::new(false, message)
+    }
+
+    /// Create a status of a service that is up or down.
+    #[must_use]
+    pub fn new(is_up: bool, message: impl Into<String>) -> Self {
+        Self {
+            health: if is_up { Health::Up } else { Health::Down },
+            message: message.into(),
+            response_time: None,
+            maintenance: false,
+        }
+    }
+
+    /// Set how well the service is doing.
+    #[must_use]
+    pub const fn with_health(mut self, health: Health) -> Self {
+        self.health = health;
+        self
+    }
+
+    /// Set how long the service took to respond.
+    #[must_use]
+    pub const fn with_response_time(mut self, response_time: std::time::Duration) -> Self {
+        self.response_time = Some(response_time);
+        self
+    }
+
+    /// Set whether the checker was in maintenance.
+    #[must_use]
+    pub const fn with_maintenance(mut self, maintenance: bool) -> Self {
+        self.maintenance = maintenance;
+        self
+    }
+
+    /// Whether the service is up, including when it is degraded.
+    #[must_use]
+    pub const fn is_up(&self) -> bool {
+        !matches!(self.health, Health::Down)
+    }
 }
 
 impl Display for Status {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        let up_or_down = if self.is_up { "Up" } else { "Down" };
-        write!(f, "{}: {}", up_or_down, self.message)
+        write!(f, "{}: {}", self.health, self.message)?;
+        if let Some(response_time) = self.response_time {
+            write!(f, " ({response_time:?})")?;
+        }
+        if self.maintenance {
+            write!(f, " (maintenance)")?;
+        }
+        Ok(())
     }
 }
 
@@ -176,16 +414,11 @@ impl FromStr for Status {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let parts: Vec<&str> = s.splitn(2, '#').collect();
         match parts.as_slice() {
-            ["up", message] => Ok(Self {
-                is_up: true,
-                message: (*message).to_string(),
-            }),
-            ["down", message] => Ok(Self {
-                is_up: false,
-                message: (*message).to_string(),
-            }),
+            ["up", message] => Ok(Self::up(*message)),
+            ["degraded", message] => Ok(Self::degraded(*message)),
+            ["down", message] => Ok(Self::down(*message)),
             _ => Err(format!(
-                "Invalid status: {s}. Expected format: <up|down>#<message>"
+                "Invalid status: {s}. Expected format: <up|degraded|down>#<message>"
             )),
         }
     }
@@ -198,7 +431,16 @@ pub trait StatusBuffer {
     fn is_empty(&self) -> bool {
         self.len() == 0
     }
-    fn from_vec(vec: VecBuffer) -> Self;
+    /// The number of statuses ever pushed, which unlike the length doesn't go down when old
+    /// statuses are dropped, for buffers that keep track of it.
+    fn head_index(&self) -> u64 {
+        self.len() as u64
+    }
+    /// Remove the statuses observed before the given time.
+    fn prune_before(&mut self, time: DateTime<Local>);
+    fn from_vec(vec: VecBuffer) -> Self
+    where
+        Self: Sized;
     fn as_vec(&self) -> VecBuffer;
 }
 
@@ -217,6 +459,10 @@ impl StatusBuffer for VecBuffer {
         self.len()
     }
 
+    fn prune_before(&mut self, time: DateTime<Local>) {
+        self.retain(|(t, _)| *t >= time);
+    }
+
     fn from_vec(vec: VecBuffer) -> Self {
         vec
     }
@@ -243,6 +489,10 @@ impl StatusBuffer for BTreeMapBuffer {
         self.len()
     }
 
+    fn prune_before(&mut self, time: DateTime<Local>) {
+        *self = self.split_off(&time);
+    }
+
     fn from_vec(vec: VecBuffer) -> Self {
         vec.into_iter().collect()
     }
@@ -4,4 +4,8 @@ pub use checker::*;
 pub mod api;
 pub use api::Info as ApiInfo;
 pub use api::Message as ApiMessage;
-pub use api::{CheckerMessage, ListMessage};
+pub use api::{
+    BuildInfo, CheckerMessage, CreationResult, EncodedMessage, Encoding, ErrorDetail,
+    ErrorResponse, GlobalMessage, GroupState, GroupStatus, LatestStatus, ListMessage, Maintenance,
+    ServerHealth, StatusRange, TransitionMessage, Uptime,
+};
@@ -1,219 +1,1080 @@
-use crate::StatusRingBuffer;
+use crate::persistence::{HistoryChange, SavedSpec};
+use crate::{config::PublicSpecFields, groups, history::HistoryBackend, Config, History};
 use axum::{
+    body::Body,
     extract::{
-        ws::{Message, WebSocket, WebSocketUpgrade},
-        Path, State,
+        ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade},
+        DefaultBodyLimit, State,
     },
-    http::StatusCode,
-    response::IntoResponse,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
     routing::{delete, get, post, put},
-    Json,
 };
 use chrono::{DateTime, Local};
 use futures::stream::SplitSink;
 use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet};
 use std::error::Error;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{mpsc, watch, RwLock};
 use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
-use tracing::{info, warn};
+use tower_http::decompression::RequestDecompressionLayer;
+use tracing::{debug, info, warn};
 
-use swec_core::{checker, ApiInfo, ApiMessage, CheckerMessage, ListMessage};
+use swec_core::{
+    checker, ApiInfo, ApiMessage, BuildInfo, CheckerMessage, CreationResult, EncodedMessage,
+    Encoding, GlobalMessage, GroupStatus, LatestStatus, ListMessage, Maintenance, ServerHealth,
+    StatusRange, TransitionMessage, Uptime,
+};
+
+pub use auth::Requester;
+pub use checker_with_sender::{CheckerWithSender, Damping};
+pub use error::{ApiError, Json, Path, Query};
 
-pub use checker_with_sender::CheckerWithSender;
+mod auth;
+mod csv;
+mod error;
+mod feed;
+mod metrics;
+mod public;
+
+use self::public::ContainsSpecs;
 
 use self::btreemap_with_sender::BTreeMapWithSender;
 
+/// The state shared by all handlers.
+pub type ApiState = (ApiInfo, Arc<Config>, Arc<RwLock<AppState>>);
+
 // The read-only API.
-pub fn read_only_router() -> axum::Router<(ApiInfo, Arc<RwLock<AppState>>)> {
+pub fn read_only_router() -> axum::Router<ApiState> {
     axum::Router::new()
         .route("/info", get(get_api_info))
+        .route("/version", get(get_version))
+        .route("/healthz", get(get_health))
+        .route("/readyz", get(get_readiness))
+        .route("/metrics", get(get_metrics))
         .route("/checkers", get(get_checkers))
         .route("/checker_names", get(get_checker_names))
+        .route("/latest_statuses", get(get_latest_statuses))
         .route("/watch", get(get_global_ws))
+        .route("/watch_all", get(get_watch_all_ws))
+        .route("/transitions", get(get_transitions_ws))
+        .route("/checkers/search", get(search_checkers))
+        .route("/groups", get(get_groups))
+        .route("/groups/:name", get(get_group))
+        .route("/tags", get(get_tags))
         .route("/checkers/:name", get(get_checker))
         .route("/checkers/:name/spec", get(get_checker_spec))
         .route("/checkers/:name/statuses", get(get_checker_statuses))
+        .route("/checkers/:name/uptime", get(get_checker_uptime))
         .route("/checkers/:name/statuses/:index", get(get_checker_status))
+        .route("/checkers/:name/statuses/at", get(get_checker_status_at))
+        .route(
+            "/checkers/:name/statuses/rollup",
+            get(get_checker_statuses_rollup),
+        )
+        .route("/checkers/:name/export", get(get_checker_export))
+        .route("/checkers/:name/statuses.jsonl", get(get_checker_export))
+        .route("/checkers/:name/statuses.csv", get(get_checker_csv))
+        .route("/checkers/:name/export.json", get(get_checker_backup))
+        .route("/checkers/:name/feed.xml", get(get_checker_feed))
         .route("/checkers/:name/watch", get(get_checker_ws))
+        .route(
+            "/checkers/:name/transitions",
+            get(get_checker_transitions_ws),
+        )
 }
 
 // The read-write API.
-pub fn read_write_router() -> axum::Router<(ApiInfo, Arc<RwLock<AppState>>)> {
-    read_only_router()
+/// With tokens, all routes that aren't in the read-only API require one of them as a bearer
+/// token.
+pub fn read_write_router(config: &Config) -> axum::Router<ApiState> {
+    let writes = axum::Router::new()
+        .route("/admin/config", get(get_config))
+        .route("/checkers", post(post_checkers))
         .route("/checkers/:name", delete(delete_checker))
         .route("/checkers/:name/spec", post(post_checker_spec))
         .route("/checkers/:name/spec", put(put_checker_spec))
+        .route("/checkers/:name/enable", post(post_checker_enable))
+        .route("/checkers/:name/disable", post(post_checker_disable))
+        .route(
+            "/checkers/:name/maintenance",
+            post(post_checker_maintenance),
+        )
         .route("/checkers/:name/statuses", post(post_checker_status))
+        .route("/checkers/:name/statuses", delete(delete_checker_statuses))
+        .route(
+            "/checkers/:name/statuses/batch",
+            // Overrides the limit below, since it is set closer to the handler.
+            post(post_checker_statuses).layer(DefaultBodyLimit::max(config.max_batch_body_size)),
+        )
+        .layer(DefaultBodyLimit::max(config.max_body_size));
+    let tokens = auth::Tokens {
+        api_token: config.api_token.clone(),
+        prefixed: config.prefixed_tokens.clone(),
+    };
+    let writes = if tokens.is_empty() {
+        writes
+    } else {
+        writes.route_layer(axum::middleware::from_fn_with_state(
+            Arc::new(tokens),
+            auth::require_token,
+        ))
+    };
+    read_only_router()
+        .merge(writes)
+        // Lets checkers on slow links compress large posts. Bodies are still bounded by the body
+        // size limit once decompressed.
+        .layer(RequestDecompressionLayer::new())
 }
 
-pub async fn get_api_info(
-    State((api_info, _)): State<(ApiInfo, Arc<RwLock<AppState>>)>,
-) -> Json<ApiInfo> {
+pub async fn get_api_info(State((api_info, _, _)): State<ApiState>) -> Json<ApiInfo> {
     Json(api_info)
 }
 
+/// Get the build of the server. The commit and build time are taken from the `SWEC_GIT_SHA` and
+/// `SWEC_BUILT_AT` environment variables at compile time, which packagers can set.
+pub async fn get_version() -> Json<BuildInfo> {
+    Json(BuildInfo {
+        swec_version: crate::VERSION.to_string(),
+        git_sha: option_env!("SWEC_GIT_SHA").map(ToString::to_string),
+        built_at: option_env!("SWEC_BUILT_AT").map(ToString::to_string),
+    })
+}
+
+/// Tell whether the server is alive. This fails with 503 if the state can't be read within a
+/// second, e.g. because a deadlock keeps it locked, instead of hanging like the other routes would.
+pub async fn get_health(
+    State((_, _, app_state)): State<ApiState>,
+) -> Result<Json<ServerHealth>, ApiError> {
+    let app_state = tokio::time::timeout(std::time::Duration::from_secs(1), app_state.read())
+        .await
+        .map_err(|_| {
+            ApiError::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "state_locked",
+                "Timed out waiting for the state lock",
+            )
+        })?;
+    Ok(Json(ServerHealth {
+        uptime_secs: app_state.uptime().as_secs(),
+        checkers: app_state.checker_count(),
+    }))
+}
+
+/// Tell whether the server is ready, i.e. whether it is able to save the checkers. It is not
+/// ready once dumps have been failing for more than `failing_dumps_tolerance` dump intervals, so
+/// that a full disk doesn't go unnoticed until a restart loses data.
+pub async fn get_readiness(
+    State((_, config, app_state)): State<ApiState>,
+) -> Result<StatusCode, ApiError> {
+    let tolerance = std::time::Duration::from_secs(config.dump_interval + config.dump_splay)
+        * config.failing_dumps_tolerance;
+    match app_state.read().await.dump_failure() {
+        Some((since, error))
+            if (Local::now() - *since)
+                .to_std()
+                .is_ok_and(|failing_for| failing_for > tolerance) =>
+        {
+            Err(ApiError::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "dumps_failing",
+                format!("Dumps have been failing since {since}, last with: {error}"),
+            ))
+        }
+        _ => Ok(StatusCode::NO_CONTENT),
+    }
+}
+
+/// Get gauges of every checker for Prometheus to scrape.
+pub async fn get_metrics(State((_, _, app_state)): State<ApiState>) -> Response {
+    let metrics = metrics::prometheus(app_state.read().await.iter_checkers());
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics,
+    )
+        .into_response()
+}
+
+pub async fn get_config(State((_, config, _)): State<ApiState>) -> Json<Config> {
+    Json((*config).clone())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CheckersQuery {
+    /// Comma-separated names of the checkers to get, instead of all of them
+    names: Option<String>,
+    /// Only get the checkers with this tag
+    tag: Option<String>,
+}
+
+/// Get all checkers, or only those named in the query, and only those with the tag in the query
+/// if there is one.
+/// When names are given, those of missing checkers map to `null`, so that a dashboard can get a
+/// handful of checkers in one request and still tell which ones don't exist. Named checkers
+/// without the tag are left out, like when no names are given.
 pub async fn get_checkers(
-    State((_, app_state)): State<(ApiInfo, Arc<RwLock<AppState>>)>,
-) -> (
-    StatusCode,
-    Json<BTreeMap<String, checker::Checker<StatusRingBuffer>>>,
-) {
-    let checkers = app_state.read().await.get_checkers();
-    (StatusCode::OK, Json(checkers))
+    State((api_info, config, app_state)): State<ApiState>,
+    Query(CheckersQuery { names, tag }): Query<CheckersQuery>,
+) -> Result<Response, ApiError> {
+    let has_tag = |checker: &checker::Checker<History>| {
+        tag.as_deref().is_none_or(|tag| checker.spec.has_tag(tag))
+    };
+    let checkers: BTreeMap<String, Option<checker::Checker<History>>> = {
+        let app_state = app_state.read().await;
+        match names {
+            Some(names) => names
+                .split(',')
+                .filter(|name| !name.is_empty())
+                .map(|name| (name.to_string(), app_state.get_checker(name).ok()))
+                .filter(|(_, checker)| checker.as_ref().is_none_or(has_tag))
+                .collect(),
+            // Filter before cloning, since histories can be long.
+            None => app_state
+                .iter_checkers()
+                .filter(|(_, checker)| has_tag(checker))
+                .map(|(name, checker)| (name.clone(), Some(checker.clone())))
+                .collect(),
+        }
+    };
+    // With long histories, this can take a while: don't hold up other requests on this worker.
+    json_blocking(public_view(&api_info, &config, checkers)).await
 }
 
-pub async fn get_checker_names(
-    State((_, app_state)): State<(ApiInfo, Arc<RwLock<AppState>>)>,
-) -> Json<Vec<String>> {
+/// Serialize a response body on a blocking thread, for bodies too large to be serialized
+/// without stalling the runtime.
+async fn json_blocking<T: Serialize + Send + 'static>(value: T) -> Result<Response, ApiError> {
+    let body = tokio::task::spawn_blocking(move || serde_json::to_vec(&value))
+        .await
+        .map_err(|e| {
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+                format!("Serialization task failed: {e}"),
+            )
+        })?
+        .map_err(|e| {
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+                format!("Failed to serialize the response: {e}"),
+            )
+        })?;
+    Ok(([(header::CONTENT_TYPE, "application/json")], body).into_response())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchQuery {
+    q: String,
+}
+
+/// Get the specs of the checkers whose name or description contains the query, ignoring case.
+pub async fn search_checkers(
+    State((api_info, config, app_state)): State<ApiState>,
+    Query(SearchQuery { q }): Query<SearchQuery>,
+) -> Json<BTreeMap<String, checker::Spec>> {
+    let q = q.to_lowercase();
+    let specs = app_state
+        .read()
+        .await
+        .checkers
+        .inner()
+        .iter()
+        .filter(|(name, checker)| {
+            name.to_lowercase().contains(&q)
+                || checker
+                    .checker()
+                    .spec
+                    .description
+                    .to_lowercase()
+                    .contains(&q)
+        })
+        .map(|(name, checker)| (name.clone(), checker.checker().spec.clone()))
+        .collect();
+    Json(public_view(&api_info, &config, specs))
+}
+
+pub async fn get_checker_names(State((_, _, app_state)): State<ApiState>) -> Json<Vec<String>> {
     Json(app_state.read().await.checkers.keys().cloned().collect())
 }
 
+pub async fn get_latest_statuses(
+    State((_, _, app_state)): State<ApiState>,
+) -> Json<BTreeMap<String, Option<LatestStatus>>> {
+    Json(app_state.read().await.get_latest_statuses())
+}
+
+/// Get the status of every group, rolled up from the latest statuses of its checkers.
+/// This and `get_group` are not found on the read-only API if groups aren't public.
+pub async fn get_groups(
+    State((api_info, config, app_state)): State<ApiState>,
+) -> Result<Json<BTreeMap<String, GroupStatus>>, ApiError> {
+    check_groups_public(&api_info, &config)?;
+    Ok(Json(app_state.read().await.get_groups(config.group_rollup)))
+}
+
+/// Make the groups routes not found when the API doesn't expose the group of checkers, since the
+/// names of groups would give them away.
+fn check_groups_public(api_info: &ApiInfo, config: &Config) -> Result<(), ApiError> {
+    if public_spec_fields(api_info, config).is_some_and(|fields| !fields.group) {
+        return Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "group_not_found",
+            "Groups are not public",
+        ));
+    }
+    Ok(())
+}
+
+/// Get the names of the checkers with each tag, for those of the tags that any checker has.
+pub async fn get_tags(
+    State((_, _, app_state)): State<ApiState>,
+) -> Json<BTreeMap<String, Vec<String>>> {
+    Json(app_state.read().await.get_tags())
+}
+
+pub async fn get_group(
+    State((api_info, config, app_state)): State<ApiState>,
+    Path(name): Path<String>,
+) -> Result<Json<GroupStatus>, ApiError> {
+    check_groups_public(&api_info, &config)?;
+    app_state
+        .read()
+        .await
+        .get_groups(config.group_rollup)
+        .remove(&name)
+        .map(Json)
+        .ok_or_else(|| {
+            ApiError::new(
+                StatusCode::NOT_FOUND,
+                "group_not_found",
+                format!("No checker is in a group named {name}"),
+            )
+        })
+}
+
 pub async fn get_checker(
-    State((_, app_state)): State<(ApiInfo, Arc<RwLock<AppState>>)>,
+    State((api_info, config, app_state)): State<ApiState>,
     Path(name): Path<String>,
-) -> (StatusCode, Json<Option<checker::Checker<StatusRingBuffer>>>) {
-    app_state.read().await.get_checker(&name).map_or_else(
-        |_| (StatusCode::NOT_FOUND, Json(None)),
-        |checker| (StatusCode::OK, Json(Some(checker))),
-    )
+) -> Result<Json<checker::Checker<History>>, ApiError> {
+    let checker = app_state.read().await.get_checker(&name)?;
+    Ok(Json(public_view(&api_info, &config, checker)))
 }
 
 pub async fn delete_checker(
-    State((_, app_state)): State<(ApiInfo, Arc<RwLock<AppState>>)>,
+    State((_, _, app_state)): State<ApiState>,
     Path(name): Path<String>,
-) -> (StatusCode, Json<Option<checker::Checker<StatusRingBuffer>>>) {
-    app_state.write().await.remove_checker(&name).map_or_else(
-        |_| (StatusCode::NOT_FOUND, Json(None)),
-        |checker| (StatusCode::OK, Json(Some(checker))),
-    )
+    requester: Requester,
+) -> Result<Json<checker::Checker<History>>, ApiError> {
+    requester.check_name(&name)?;
+    let checker = app_state.write().await.remove_checker(&name)?;
+    info!(target: "audit", action = "delete", checker = name, requester = %requester, "Checker deleted");
+    Ok(Json(checker))
 }
 
+/// Clear the history of a checker, keeping the checker itself.
+pub async fn delete_checker_statuses(
+    State((_, _, app_state)): State<ApiState>,
+    Path(name): Path<String>,
+    requester: Requester,
+) -> Result<StatusCode, ApiError> {
+    requester.check_name(&name)?;
+    app_state
+        .write()
+        .await
+        .get_checker_with_sender_mut(&name)?
+        .clear_statuses();
+    info!(target: "audit", action = "clear_statuses", checker = name, requester = %requester, "Checker history cleared");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Put a checker in maintenance until the given time, or take it out of maintenance with no time,
+/// e.g. around a deployment. See `CheckerMessage::MaintenanceChanged`.
+pub async fn post_checker_maintenance(
+    State((_, _, app_state)): State<ApiState>,
+    Path(name): Path<String>,
+    requester: Requester,
+    Json(maintenance): Json<Maintenance>,
+) -> Result<Json<Maintenance>, ApiError> {
+    requester.check_name(&name)?;
+    app_state
+        .write()
+        .await
+        .get_checker_with_sender_mut(&name)?
+        .set_maintenance(maintenance.until);
+    match maintenance.until {
+        Some(until) => info!(
+            target: "audit",
+            action = "maintenance",
+            checker = name,
+            requester = %requester,
+            "Checker in maintenance until {until}"
+        ),
+        None => info!(
+            target: "audit",
+            action = "maintenance",
+            checker = name,
+            requester = %requester,
+            "Checker out of maintenance"
+        ),
+    }
+    Ok(Json(maintenance))
+}
+
+/// Get the spec of a checker, with a weak `ETag` so that clients polling it can send
+/// `If-None-Match` and get a 304 instead of the same spec as long as it doesn't change.
 pub async fn get_checker_spec(
-    State((_, app_state)): State<(ApiInfo, Arc<RwLock<AppState>>)>,
+    State((api_info, config, app_state)): State<ApiState>,
     Path(name): Path<String>,
-) -> (StatusCode, Json<Option<checker::Spec>>) {
-    app_state.read().await.get_checker(&name).map_or_else(
-        |_| (StatusCode::NOT_FOUND, Json(None)),
-        |checker| (StatusCode::OK, Json(Some(checker.spec))),
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let spec = app_state
+        .read()
+        .await
+        .get_checker_with_sender(&name)?
+        .checker()
+        .spec
+        .clone();
+    let body = serde_json::to_vec(&public_view(&api_info, &config, spec))
+        .expect("Specs can always be serialized");
+    let etag = etag(&body);
+    let headers_out = [(header::ETAG, etag.clone())];
+    if headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .any(|value| etag_matches(value, &etag))
+    {
+        return Ok((StatusCode::NOT_MODIFIED, headers_out).into_response());
+    }
+    Ok((
+        headers_out,
+        [(header::CONTENT_TYPE, "application/json")],
+        body,
     )
+        .into_response())
+}
+
+/// A weak entity tag for a response body. It only has to be stable for as long as the server
+/// runs, so a hash of the body is enough.
+fn etag(body: &[u8]) -> String {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    body.hash(&mut hasher);
+    format!("W/\"{:016x}\"", hasher.finish())
+}
+
+/// Whether an `If-None-Match` header matches the entity tag, with the weak comparison that the
+/// header calls for.
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    if_none_match
+        .split(',')
+        .any(|tag| tag.trim() == "*" || opaque(tag) == opaque(etag))
+}
+
+/// The spec fields the API exposes, or `None` if it exposes all of them.
+fn public_spec_fields(api_info: &ApiInfo, config: &Config) -> Option<PublicSpecFields> {
+    (!api_info.writable).then_some(config.public_spec_fields)
+}
+
+/// Strip the spec fields the API doesn't expose from a value about to be sent.
+fn public_view<T: ContainsSpecs>(api_info: &ApiInfo, config: &Config, mut value: T) -> T {
+    if let Some(fields) = public_spec_fields(api_info, config) {
+        value.strip_specs(fields);
+    }
+    value
 }
 
 pub async fn post_checker_spec(
-    State((_, app_state)): State<(ApiInfo, Arc<RwLock<AppState>>)>,
+    State((_, config, app_state)): State<ApiState>,
     Path(name): Path<String>,
+    requester: Requester,
     Json(spec): Json<checker::Spec>,
-) -> (StatusCode, Json<Option<checker::Spec>>) {
+) -> Result<(StatusCode, Json<checker::Spec>), ApiError> {
+    requester.check_name(&name)?;
+    if config.validate_specs {
+        spec.validate()?;
+    }
     app_state
         .write()
         .await
-        .add_checker(name, spec.clone())
-        .map_or_else(
-            |_| (StatusCode::CONFLICT, Json(None)),
-            |()| (StatusCode::CREATED, Json(Some(spec))),
-        )
+        .add_checker(name.clone(), spec.clone())?;
+    info!(target: "audit", action = "create", checker = name, requester = %requester, "Checker created");
+    Ok((StatusCode::CREATED, Json(spec)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PostCheckersQuery {
+    /// Whether to create none of the checkers if any of them can't be created
+    #[serde(default)]
+    atomic: bool,
+}
+
+/// Create many checkers at once, e.g. to set up a fresh server.
+/// Each checker is created unless its spec is invalid, its name is taken or outside of the prefix
+/// of the token, and the result is given for each of them. With `?atomic=true`, none are created
+/// if any of them can't be, and the first failure is returned as an error instead.
+pub async fn post_checkers(
+    State((_, config, app_state)): State<ApiState>,
+    Query(PostCheckersQuery { atomic }): Query<PostCheckersQuery>,
+    requester: Requester,
+    Json(specs): Json<BTreeMap<String, checker::Spec>>,
+) -> Result<Json<BTreeMap<String, CreationResult>>, ApiError> {
+    // Hold the lock throughout, so that names can't be taken between the check and the creation.
+    let mut app_state = app_state.write().await;
+    if atomic {
+        for (name, spec) in &specs {
+            requester.check_name(name)?;
+            if config.validate_specs {
+                spec.validate()?;
+            }
+            if app_state.get_checker_with_sender(name).is_ok() {
+                return Err(CheckerAlreadyExists(name.clone()).into());
+            }
+        }
+    }
+    let results = specs
+        .into_iter()
+        .map(|(name, spec)| {
+            let validation = config.validate_specs.then(|| spec.validate());
+            let result = if requester.check_name(&name).is_err() {
+                CreationResult::Forbidden
+            } else if let Some(Err(checker::InvalidSpec(reason))) = validation {
+                CreationResult::Invalid { reason }
+            } else if let Err(CheckerAlreadyExists(_)) = app_state.add_checker(name.clone(), spec) {
+                CreationResult::Conflict
+            } else {
+                info!(target: "audit", action = "create", checker = name, requester = %requester, "Checker created");
+                CreationResult::Created
+            };
+            (name, result)
+        })
+        .collect();
+    Ok(Json(results))
 }
 
+/// Update the spec of a checker, or create the checker with an empty history if there is none by
+/// that name, so that provisioning can be done without knowing which checkers exist. Responds
+/// with 201 if the checker was created.
 pub async fn put_checker_spec(
-    State((_, app_state)): State<(ApiInfo, Arc<RwLock<AppState>>)>,
+    State((_, config, app_state)): State<ApiState>,
     Path(name): Path<String>,
+    requester: Requester,
     Json(spec): Json<checker::Spec>,
-) -> (StatusCode, Json<Option<checker::Spec>>) {
-    app_state
-        .write()
-        .await
-        .get_checker_with_sender_mut(&name)
-        .map_or_else(
-            |_| (StatusCode::NOT_FOUND, Json(None)),
-            |checker| {
-                checker.update_spec(spec.clone());
-                (StatusCode::OK, Json(Some(spec)))
-            },
-        )
+) -> Result<(StatusCode, Json<checker::Spec>), ApiError> {
+    requester.check_name(&name)?;
+    if config.validate_specs {
+        spec.validate()?;
+    }
+    let mut app_state = app_state.write().await;
+    let history_len = app_state.history_len_of(&spec);
+    if let Ok(checker) = app_state.get_checker_with_sender_mut(&name) {
+        checker.update_spec(spec.clone(), history_len);
+        info!(target: "audit", action = "update", checker = name, requester = %requester, "Checker spec updated");
+        return Ok((StatusCode::OK, Json(spec)));
+    }
+    app_state.add_checker(name.clone(), spec.clone())?;
+    info!(target: "audit", action = "create", checker = name, requester = %requester, "Checker created");
+    Ok((StatusCode::CREATED, Json(spec)))
+}
+
+pub async fn post_checker_enable(
+    State((_, _, app_state)): State<ApiState>,
+    Path(name): Path<String>,
+    requester: Requester,
+) -> Result<Json<checker::Spec>, ApiError> {
+    set_checker_enabled(&app_state, &name, true, &requester).await
+}
+
+pub async fn post_checker_disable(
+    State((_, _, app_state)): State<ApiState>,
+    Path(name): Path<String>,
+    requester: Requester,
+) -> Result<Json<checker::Spec>, ApiError> {
+    set_checker_enabled(&app_state, &name, false, &requester).await
+}
+
+async fn set_checker_enabled(
+    app_state: &RwLock<AppState>,
+    name: &str,
+    enabled: bool,
+    requester: &Requester,
+) -> Result<Json<checker::Spec>, ApiError> {
+    requester.check_name(name)?;
+    let mut app_state = app_state.write().await;
+    let checker = app_state.get_checker_with_sender_mut(name)?;
+    let spec = checker::Spec {
+        enabled,
+        ..checker.checker().spec.clone()
+    };
+    let history_len = checker.checker().statuses.capacity();
+    checker.update_spec(spec.clone(), history_len);
+    info!(
+        target: "audit",
+        action = if enabled { "enable" } else { "disable" },
+        checker = name,
+        requester = %requester,
+        "Checker {}",
+        if enabled { "enabled" } else { "disabled" },
+    );
+    Ok(Json(spec))
 }
 
+/// Bounds of the statuses to return, both optional: `since` is inclusive and `until` is
+/// exclusive. They can also be given as `from` and `to`.
+#[derive(Debug, Deserialize)]
+pub struct TimeRange {
+    #[serde(alias = "from")]
+    since: Option<DateTime<Local>>,
+    #[serde(alias = "to")]
+    until: Option<DateTime<Local>>,
+}
+
+/// Bounds of the statuses to return like in `TimeRange`, and of the page of them to return.
+#[derive(Debug, Deserialize)]
+pub struct StatusesQuery {
+    since: Option<DateTime<Local>>,
+    until: Option<DateTime<Local>>,
+    /// Maximum number of statuses to return
+    limit: Option<usize>,
+    /// Number of statuses to skip, counting back from the newest
+    offset: Option<usize>,
+    /// Only return the statuses added since this index, the `head` of a previous response,
+    /// instead of using the other bounds
+    since_index: Option<u64>,
+}
+
+/// Get the statuses of a checker, optionally in a time range and paginated.
+/// Pages are counted from the newest status, but the statuses of a page are still sorted oldest
+/// first. The `X-Total-Count` header has the number of statuses in the range, to page through.
+/// See `StatusRange` for how a checker without statuses is told apart from one without statuses
+/// in the range.
+/// Pollers can instead pass the `head` of the previous response as `since_index`, to only get the
+/// statuses added since. Only the statuses still in the history are returned, and all of them
+/// are if the index is past the head, e.g. because the server restarted without the statuses
+/// added since its last dump.
 pub async fn get_checker_statuses(
-    State((_, app_state)): State<(ApiInfo, Arc<RwLock<AppState>>)>,
+    State((_, _, app_state)): State<ApiState>,
     Path(name): Path<String>,
-) -> (
-    StatusCode,
-    Json<Option<Vec<(DateTime<Local>, checker::Status)>>>,
-) {
-    app_state.read().await.get_checker(&name).map_or_else(
-        |_| (StatusCode::NOT_FOUND, Json(None)),
-        |checker| (StatusCode::OK, Json(Some(checker.statuses.collect()))),
+    Query(StatusesQuery {
+        since,
+        until,
+        limit,
+        offset,
+        since_index,
+    }): Query<StatusesQuery>,
+) -> Result<Response, ApiError> {
+    let app_state = app_state.read().await;
+    let statuses = &app_state.get_checker_with_sender(&name)?.checker().statuses;
+    if let Some(since_index) = since_index {
+        if since.is_some() || until.is_some() || limit.is_some() || offset.is_some() {
+            return Err(ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "invalid_query",
+                "since_index can't be combined with other bounds",
+            ));
+        }
+        let page: Vec<_> = statuses
+            .iter_since(since_index)
+            .map(|(time, status)| (time, status.clone()))
+            .collect();
+        return Ok((
+            [(TOTAL_COUNT_HEADER, page.len().to_string())],
+            Json(StatusRange {
+                statuses: page,
+                total: statuses.len(),
+                head: statuses.head_index(),
+            }),
+        )
+            .into_response());
+    }
+    let in_range = statuses.range(since, until).count();
+    let mut page: Vec<_> = statuses
+        .range(since, until)
+        .rev()
+        .skip(offset.unwrap_or(0))
+        .take(limit.unwrap_or(usize::MAX))
+        .map(|(time, status)| (time, status.clone()))
+        .collect();
+    page.reverse();
+    Ok((
+        [(TOTAL_COUNT_HEADER, in_range.to_string())],
+        Json(StatusRange {
+            statuses: page,
+            total: statuses.len(),
+            head: statuses.head_index(),
+        }),
     )
+        .into_response())
+}
+
+const TOTAL_COUNT_HEADER: header::HeaderName = header::HeaderName::from_static("x-total-count");
+
+/// Get how long a checker was up and down, optionally in a time range, e.g. for an SLA.
+/// Statuses are weighted by how long they lasted rather than counted. The share of time up is
+/// given both as `uptime`, from 0 to 1, and as `percent`, from 0 to 100, which are `null` when
+/// nothing is known about the range, instead of a division by zero.
+pub async fn get_checker_uptime(
+    State((_, config, app_state)): State<ApiState>,
+    Path(name): Path<String>,
+    Query(TimeRange { since, until }): Query<TimeRange>,
+) -> Result<Json<Uptime>, ApiError> {
+    let max_gap = config
+        .max_status_gap
+        .and_then(|secs| chrono::Duration::try_seconds(i64::try_from(secs).ok()?));
+    Ok(Json(
+        app_state
+            .read()
+            .await
+            .get_checker_with_sender(&name)?
+            .checker()
+            .statuses
+            .uptime(since, until, Local::now(), max_gap),
+    ))
+}
+
+/// Stream the whole history of a checker as newline-delimited JSON, oldest status first.
+/// The history is copied out of the state so that the lock isn't held while the body is sent,
+/// but each line is only serialized when it is sent, so the response is never buffered whole.
+pub async fn get_checker_export(
+    State((_, _, app_state)): State<ApiState>,
+    Path(name): Path<String>,
+) -> Result<Response, ApiError> {
+    let statuses: Vec<_> = app_state
+        .read()
+        .await
+        .get_checker_with_sender(&name)?
+        .checker()
+        .statuses
+        .as_vec();
+    let lines = futures::stream::iter(statuses).map(|status| {
+        serde_json::to_vec(&status).map(|mut line| {
+            line.push(b'\n');
+            line
+        })
+    });
+    Ok((
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(lines),
+    )
+        .into_response())
+}
+
+/// Stream the whole history of a checker as CSV, oldest status first, e.g. to open it in a
+/// spreadsheet. Like `get_checker_export`, rows are only formatted when they are sent.
+pub async fn get_checker_csv(
+    State((_, _, app_state)): State<ApiState>,
+    Path(name): Path<String>,
+) -> Result<Response, ApiError> {
+    let statuses: Vec<_> = app_state
+        .read()
+        .await
+        .get_checker_with_sender(&name)?
+        .checker()
+        .statuses
+        .as_vec();
+    let rows = futures::stream::once(async { csv::HEADER.to_string() })
+        .chain(futures::stream::iter(statuses).map(|(time, status)| csv::row(&time, &status)))
+        .map(Ok::<_, std::convert::Infallible>);
+    Ok((
+        [(header::CONTENT_TYPE, "text/csv; charset=utf-8")],
+        Body::from_stream(rows),
+    )
+        .into_response())
+}
+
+/// Get the spec and whole history of a checker as a JSON file to download, e.g. to back it up
+/// before a risky change.
+pub async fn get_checker_backup(
+    State((api_info, config, app_state)): State<ApiState>,
+    Path(name): Path<String>,
+) -> Result<Response, ApiError> {
+    let checker = app_state.read().await.get_checker(&name)?;
+    let mut response = json_blocking(public_view(&api_info, &config, checker)).await?;
+    // Keep the file name to characters that are safe in a header and on any file system.
+    let file_name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "-_.".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    response.headers_mut().insert(
+        header::CONTENT_DISPOSITION,
+        header::HeaderValue::from_str(&format!("attachment; filename=\"{file_name}.json\""))
+            .expect("Sanitized file names are valid in headers"),
+    );
+    Ok(response)
+}
+
+/// Get an Atom feed of the times the checker went up or down, so that incidents can be followed
+/// from any feed reader.
+pub async fn get_checker_feed(
+    State((_, _, app_state)): State<ApiState>,
+    Path(name): Path<String>,
+) -> Result<Response, ApiError> {
+    let app_state = app_state.read().await;
+    let checker = app_state.get_checker_with_sender(&name)?.checker();
+    let feed = feed::atom_feed(&name, &checker.spec, checker.statuses.as_ref());
+    Ok(([(header::CONTENT_TYPE, "application/atom+xml")], feed).into_response())
 }
 
+/// Get the status at the given index, counting from the newest status, which is at index 0.
+/// Indices that aren't non-negative integers are rejected with 400 (`invalid_index`), and
+/// indices beyond the history with 404 (`status_not_found`).
 pub async fn get_checker_status(
-    State((_, app_state)): State<(ApiInfo, Arc<RwLock<AppState>>)>,
-    Path((name, index)): Path<(String, usize)>,
-) -> (StatusCode, Json<Option<(DateTime<Local>, checker::Status)>>) {
-    app_state.read().await.get_checker(&name).map_or_else(
-        |_| (StatusCode::NOT_FOUND, Json(None)),
-        |checker| {
-            checker.statuses.iter().rev().nth(index).map_or_else(
-                || (StatusCode::NOT_FOUND, Json(None)),
-                |status| (StatusCode::OK, Json(Some(status.clone()))),
+    State((_, _, app_state)): State<ApiState>,
+    Path((name, index)): Path<(String, String)>,
+) -> Result<Json<(DateTime<Local>, checker::Status)>, ApiError> {
+    let index = index.parse::<usize>().map_err(|_| {
+        ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_index",
+            format!("Invalid index {index}, expected a non-negative integer"),
+        )
+    })?;
+    let app_state = app_state.read().await;
+    let statuses = &app_state.get_checker_with_sender(&name)?.checker().statuses;
+    let status = statuses.iter().rev().nth(index);
+    status
+        .map(|(time, status)| Json((time, status.clone())))
+        .ok_or_else(|| {
+            ApiError::new(
+                StatusCode::NOT_FOUND,
+                "status_not_found",
+                format!(
+                    "No status at index {index} (history has {} entries)",
+                    statuses.len()
+                ),
             )
-        },
-    )
+        })
 }
 
+#[derive(Debug, Deserialize)]
+pub struct StatusAtQuery {
+    time: DateTime<Local>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RollupQuery {
+    /// Length of the buckets, e.g. `5m` or `1h`
+    bucket: String,
+}
+
+/// Get the history of a checker with one status per time bucket, the worst one of the bucket.
+/// See `StatusRingBuffer::downsample`.
+pub async fn get_checker_statuses_rollup(
+    State((_, _, app_state)): State<ApiState>,
+    Path(name): Path<String>,
+    Query(RollupQuery { bucket }): Query<RollupQuery>,
+) -> Result<Json<Vec<(DateTime<Local>, checker::Status)>>, ApiError> {
+    let invalid_bucket = |reason: String| {
+        ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "invalid_query",
+            format!("Invalid bucket {bucket}: {reason}"),
+        )
+    };
+    let duration = humantime::parse_duration(&bucket)
+        .map_err(|e| invalid_bucket(e.to_string()))
+        .and_then(|duration| {
+            chrono::Duration::from_std(duration).map_err(|e| invalid_bucket(e.to_string()))
+        })?;
+    if duration.num_milliseconds() < 1 {
+        return Err(invalid_bucket("buckets must last at least 1ms".to_string()));
+    }
+    Ok(Json(
+        app_state
+            .read()
+            .await
+            .get_checker_with_sender(&name)?
+            .checker()
+            .statuses
+            .downsample(duration),
+    ))
+}
+
+/// Get the status that was current at the given time.
+pub async fn get_checker_status_at(
+    State((_, _, app_state)): State<ApiState>,
+    Path(name): Path<String>,
+    Query(StatusAtQuery { time }): Query<StatusAtQuery>,
+) -> Result<Json<(DateTime<Local>, checker::Status)>, ApiError> {
+    app_state
+        .read()
+        .await
+        .get_checker_with_sender(&name)?
+        .checker()
+        .statuses
+        .status_at(time)
+        .map(|(time, status)| Json((time, status.clone())))
+        .ok_or_else(|| {
+            ApiError::new(
+                StatusCode::NOT_FOUND,
+                "status_not_found",
+                format!("No status at or before {time}"),
+            )
+        })
+}
+
+/// A posted status, either alone or with the time it was observed at, e.g. when backfilling.
+/// Statuses without a time are recorded at the time they are received.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum PostedStatus {
+    Timed(DateTime<Local>, checker::Status),
+    Untimed(checker::Status),
+}
+
+/// The body of a status post: a single status, or an array of them to post several at once, e.g.
+/// after a checker couldn't reach the server for a while.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum PostedStatuses {
+    One(PostedStatus),
+    Many(Vec<PostedStatus>),
+}
+
+/// Reject statuses with messages longer than `max_message_len`, which would otherwise be kept in
+/// memory and in dumps for as long as they are in the history.
+fn check_message_len(status: &checker::Status, config: &Config) -> Result<(), ApiError> {
+    if status.message.len() > config.max_message_len {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "message_too_long",
+            format!(
+                "Status message is {} bytes long, the maximum is {}",
+                status.message.len(),
+                config.max_message_len
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Add the posted statuses, and respond with the single status or with all of them and their
+/// times, depending on what was posted.
 pub async fn post_checker_status(
-    State((_, app_state)): State<(ApiInfo, Arc<RwLock<AppState>>)>,
+    State((_, config, app_state)): State<ApiState>,
     Path(name): Path<String>,
-    Json(status): Json<checker::Status>,
-) -> (StatusCode, Json<Option<checker::Status>>) {
+    requester: Requester,
+    Json(statuses): Json<PostedStatuses>,
+) -> Result<Response, ApiError> {
+    requester.check_name(&name)?;
+    let mut app_state = app_state.write().await;
+    let checker = app_state.get_checker_with_sender_mut(&name)?;
+    match statuses {
+        PostedStatuses::One(PostedStatus::Timed(time, status)) => {
+            check_message_len(&status, &config)?;
+            checker.add_status_at(time, status.clone(), config.out_of_order_statuses)?;
+            Ok((StatusCode::CREATED, Json(status)).into_response())
+        }
+        PostedStatuses::One(PostedStatus::Untimed(status)) => {
+            check_message_len(&status, &config)?;
+            checker.add_status(status.clone(), config.out_of_order_statuses)?;
+            Ok((StatusCode::CREATED, Json(status)).into_response())
+        }
+        PostedStatuses::Many(statuses) => {
+            // Untimed statuses of the same post all get the same time, which keeps them in order.
+            let now = Local::now();
+            let statuses = statuses
+                .into_iter()
+                .map(|status| match status {
+                    PostedStatus::Timed(time, status) => (time, status),
+                    PostedStatus::Untimed(status) => (now, status),
+                })
+                .collect::<Vec<_>>();
+            for (_, status) in &statuses {
+                check_message_len(status, &config)?;
+            }
+            // Sends one `AddedStatus` per status, as if they had been posted one by one.
+            checker.add_statuses_at(&statuses, config.out_of_order_statuses)?;
+            Ok((StatusCode::CREATED, Json(statuses)).into_response())
+        }
+    }
+}
+
+pub async fn post_checker_statuses(
+    State((_, config, app_state)): State<ApiState>,
+    Path(name): Path<String>,
+    requester: Requester,
+    Json(statuses): Json<Vec<(DateTime<Local>, checker::Status)>>,
+) -> Result<(StatusCode, Json<Vec<(DateTime<Local>, checker::Status)>>), ApiError> {
+    requester.check_name(&name)?;
+    for (_, status) in &statuses {
+        check_message_len(status, &config)?;
+    }
     app_state
         .write()
         .await
-        .get_checker_with_sender_mut(&name)
-        .map_or_else(
-            |_| (StatusCode::NOT_FOUND, Json(None)),
-            |w| {
-                w.add_status(status.clone());
-                (StatusCode::CREATED, Json(Some(status)))
-            },
-        )
+        .get_checker_with_sender_mut(&name)?
+        .add_statuses_at(&statuses, config.out_of_order_statuses)?;
+    Ok((StatusCode::CREATED, Json(statuses)))
 }
 
 pub async fn get_checker_ws(
     ws: WebSocketUpgrade,
-    State((_, app_state)): State<(ApiInfo, Arc<RwLock<AppState>>)>,
+    State((api_info, config, app_state)): State<ApiState>,
     Path(name): Path<String>,
-) -> impl IntoResponse {
+) -> Result<impl IntoResponse, ApiError> {
     // The `Initial` message we send is meant to avoid race conditions where the client would first
     // ask for the current state and then subscribe to updates. This way, the client can just
     // subscribe and get the current state in one go.
     // The fact that we subscribe and create the `Initial` message in the same atomic operation is
     // important to make sure there is no race condition here.
-    let res = app_state
+    let shutdown = app_state.read().await.subscribe_shutdown();
+    let (rx, initial_message) = app_state
         .read()
         .await
         .get_checker_with_sender(&name)
         .map(|w| {
             (
                 w.subscribe(),
-                CheckerMessage::Initial(
-                    w.checker().spec.clone(),
-                    w.checker().statuses.iter().next_back().cloned(),
-                ),
+                CheckerMessage::Initial {
+                    spec: w.checker().spec.clone(),
+                    latest: w
+                        .checker()
+                        .statuses
+                        .iter()
+                        .next_back()
+                        .map(|(time, status)| (time, status.clone())),
+                },
             )
-        });
+        })?;
+    let fields = public_spec_fields(&api_info, &config);
+    let heartbeat = Heartbeat::from_config(&config);
 
-    if let Ok((rx, initial_message)) = res {
-        ws.on_upgrade(move |socket| handle_ws(socket, rx, initial_message))
-    } else {
-        StatusCode::NOT_FOUND.into_response()
-    }
+    Ok(ws
+        .protocols([Encoding::MESSAGEPACK_PROTOCOL])
+        .on_upgrade(move |socket| {
+            handle_ws(socket, rx, initial_message, shutdown, fields, heartbeat)
+        }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GlobalWsQuery {
+    /// Whether to also send the messages of every checker, like `/watch_all`
+    #[serde(default)]
+    statuses: bool,
 }
 
+/// Watch the list of checkers, and with `?statuses=true` all checkers as well, which is the same
+/// as watching `/watch_all`.
 pub async fn get_global_ws(
     ws: WebSocketUpgrade,
-    State((_, app_state)): State<(ApiInfo, Arc<RwLock<AppState>>)>,
-) -> impl IntoResponse {
+    State(state): State<ApiState>,
+    Query(GlobalWsQuery { statuses }): Query<GlobalWsQuery>,
+) -> Response {
+    if statuses {
+        return get_watch_all_ws(ws, State(state)).await.into_response();
+    }
+    let (_, config, app_state) = state;
+    let heartbeat = Heartbeat::from_config(&config);
     let (rx, initial_checkers): (
         tokio::sync::broadcast::Receiver<ListMessage>,
         BTreeSet<String>,
@@ -222,39 +1083,203 @@ pub async fn get_global_ws(
         (c.subscribe(), c.keys().cloned().collect())
     };
 
-    let initial_message = ListMessage::Initial(initial_checkers);
+    let initial_message = ListMessage::Initial {
+        names: initial_checkers,
+    };
+    let shutdown = app_state.read().await.subscribe_shutdown();
 
-    ws.on_upgrade(move |socket| handle_ws(socket, rx, initial_message))
+    ws.protocols([Encoding::MESSAGEPACK_PROTOCOL])
+        .on_upgrade(move |socket| handle_ws(socket, rx, initial_message, shutdown, None, heartbeat))
+        .into_response()
 }
 
-pub async fn handle_ws<M: ApiMessage + 'static>(
+/// Watch the list of checkers and all checkers over a single websocket.
+pub async fn get_watch_all_ws(
+    ws: WebSocketUpgrade,
+    State((api_info, config, app_state)): State<ApiState>,
+) -> impl IntoResponse {
+    let (rx, initial_message, shutdown) = {
+        let app_state = app_state.read().await;
+        let (rx, initial_message) = app_state.subscribe_global();
+        (rx, initial_message, app_state.subscribe_shutdown())
+    };
+    let fields = public_spec_fields(&api_info, &config);
+    let heartbeat = Heartbeat::from_config(&config);
+
+    ws.protocols([Encoding::MESSAGEPACK_PROTOCOL])
+        .on_upgrade(move |socket| {
+            handle_ws(socket, rx, initial_message, shutdown, fields, heartbeat)
+        })
+}
+
+/// Watch the transitions of all checkers, without their other events.
+pub async fn get_transitions_ws(
+    ws: WebSocketUpgrade,
+    State((_, config, app_state)): State<ApiState>,
+) -> impl IntoResponse {
+    let (rx, initial_message, shutdown) = {
+        let app_state = app_state.read().await;
+        let (rx, initial_message) = app_state.subscribe_transitions();
+        (rx, initial_message, app_state.subscribe_shutdown())
+    };
+    let heartbeat = Heartbeat::from_config(&config);
+
+    ws.protocols([Encoding::MESSAGEPACK_PROTOCOL])
+        .on_upgrade(move |socket| handle_ws(socket, rx, initial_message, shutdown, None, heartbeat))
+}
+
+/// Watch the transitions of a checker, without its other events.
+pub async fn get_checker_transitions_ws(
+    ws: WebSocketUpgrade,
+    State((_, config, app_state)): State<ApiState>,
+    Path(name): Path<String>,
+) -> Result<impl IntoResponse, ApiError> {
+    let (rx, initial_message, shutdown) = {
+        let app_state = app_state.read().await;
+        let w = app_state.get_checker_with_sender(&name)?;
+        let initial_message = TransitionMessage::Initial {
+            states: BTreeMap::from([(name, w.is_up())]),
+        };
+        (
+            w.subscribe_transitions(),
+            initial_message,
+            app_state.subscribe_shutdown(),
+        )
+    };
+    let heartbeat = Heartbeat::from_config(&config);
+
+    Ok(ws
+        .protocols([Encoding::MESSAGEPACK_PROTOCOL])
+        .on_upgrade(move |socket| {
+            handle_ws(socket, rx, initial_message, shutdown, None, heartbeat)
+        }))
+}
+
+/// How the server checks that websocket clients are still there.
+/// Pings also keep connections going through proxies that close idle ones.
+#[derive(Debug, Clone, Copy)]
+pub struct Heartbeat {
+    /// Time between two pings
+    interval: std::time::Duration,
+    /// Time the client has to answer a ping before the connection is dropped
+    timeout: std::time::Duration,
+}
+
+impl Heartbeat {
+    fn from_config(config: &Config) -> Option<Self> {
+        config.ws_ping_interval.map(|interval| Self {
+            interval: std::time::Duration::from_secs(interval),
+            timeout: std::time::Duration::from_secs(config.ws_pong_timeout),
+        })
+    }
+}
+
+/// Forward messages to a websocket until either side closes it.
+/// `public_fields` are the spec fields the API exposes, if it doesn't expose all of them.
+/// With a `heartbeat`, the client is pinged regularly, and the connection is dropped if it stops
+/// answering.
+pub async fn handle_ws<M: ApiMessage + ContainsSpecs + 'static>(
     socket: WebSocket,
     broadcast_rx: tokio::sync::broadcast::Receiver<M>,
     initial_message: M,
+    mut shutdown: watch::Receiver<bool>,
+    public_fields: Option<PublicSpecFields>,
+    heartbeat: Option<Heartbeat>,
 ) {
-    async fn send<M: serde::Serialize + Send>(
+    async fn send<M: ApiMessage + ContainsSpecs>(
         tx: &mut SplitSink<WebSocket, Message>,
-        msg: M,
-    ) -> Result<(), Box<dyn Error>> {
-        let msg = serde_json::to_string(&msg)?;
-        tx.send(Message::Text(msg)).await?;
+        mut msg: M,
+        encoding: Encoding,
+        public_fields: Option<PublicSpecFields>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if let Some(fields) = public_fields {
+            msg.strip_specs(fields);
+        }
+        let msg = match msg.encode(encoding)? {
+            EncodedMessage::Text(text) => Message::Text(text),
+            EncodedMessage::Binary(bytes) => Message::Binary(bytes),
+        };
+        tx.send(msg).await?;
         Ok(())
     }
+    async fn shutting_down(shutdown: &mut watch::Receiver<bool>) {
+        // An error means the server is gone, which is as good as shutting down.
+        let _ = shutdown.wait_for(|&shutting_down| shutting_down).await;
+    }
+    async fn tick(interval: &mut Option<tokio::time::Interval>) {
+        match interval {
+            Some(interval) => {
+                interval.tick().await;
+            }
+            None => std::future::pending().await,
+        }
+    }
+    let encoding = Encoding::from_protocol(socket.protocol().and_then(|p| p.to_str().ok()));
     let (mut socket_tx, mut socket_rx) = socket.split();
 
     let mut broadcast_rx = BroadcastStream::new(broadcast_rx);
+    let mut ping_interval = heartbeat.map(|heartbeat| {
+        tokio::time::interval_at(
+            tokio::time::Instant::now() + heartbeat.interval,
+            heartbeat.interval,
+        )
+    });
+    // When the unanswered ping was sent, if any.
+    let mut pong_deadline: Option<tokio::time::Instant> = None;
+    let pong_received = Arc::new(tokio::sync::Notify::new());
+    let timed_out = Arc::new(tokio::sync::Notify::new());
+    let (task_pong_received, task_timed_out) = (pong_received.clone(), timed_out.clone());
 
-    send(&mut socket_tx, initial_message)
+    send(&mut socket_tx, initial_message, encoding, public_fields)
         .await
         .unwrap_or_else(|e| {
             warn!(target: "websockets", "Failed to send initial message: {e}");
         });
 
     let handle = tokio::spawn(async move {
-        while let Some(msg) = broadcast_rx.next().await {
+        loop {
+            #[allow(clippy::redundant_pub_crate)]
+            let msg = tokio::select! {
+                msg = broadcast_rx.next() => msg,
+                () = tick(&mut ping_interval) => {
+                    if pong_deadline.is_none() {
+                        if let Err(e) = socket_tx.send(Message::Ping(Vec::new())).await {
+                            warn!(target: "websockets", "Failed to send ping: {e}");
+                            break;
+                        }
+                        pong_deadline = heartbeat
+                            .map(|heartbeat| tokio::time::Instant::now() + heartbeat.timeout);
+                    }
+                    continue;
+                }
+                () = tokio::time::sleep_until(
+                    pong_deadline.unwrap_or_else(tokio::time::Instant::now)
+                ), if pong_deadline.is_some() => {
+                    info!(target: "websockets", "Client didn't answer ping, dropping the connection");
+                    task_timed_out.notify_one();
+                    return;
+                }
+                () = task_pong_received.notified() => {
+                    pong_deadline = None;
+                    continue;
+                }
+                () = shutting_down(&mut shutdown) => {
+                    // Tell the client we are going away, and let it close the connection so that
+                    // it doesn't see an error.
+                    let frame = CloseFrame {
+                        code: close_code::AWAY,
+                        reason: "Server shutting down".into(),
+                    };
+                    if let Err(e) = socket_tx.send(Message::Close(Some(frame))).await {
+                        warn!(target: "websockets", "Failed to send close frame: {e}");
+                    }
+                    return;
+                }
+            };
+            let Some(msg) = msg else { break };
             match msg {
                 Ok(msg) => {
-                    if let Err(e) = send(&mut socket_tx, msg).await {
+                    if let Err(e) = send(&mut socket_tx, msg, encoding, public_fields).await {
                         warn!(target: "websockets", "Failed to send websocket message: {e}");
                         break;
                     }
@@ -262,7 +1287,9 @@ pub async fn handle_ws<M: ApiMessage + 'static>(
                 Err(e) => match e {
                     BroadcastStreamRecvError::Lagged(n) => {
                         warn!(target: "websockets", "Lagged and skipped {n} messages. Informing client.");
-                        if let Err(e) = send(&mut socket_tx, CheckerMessage::Lagged(n)).await {
+                        if let Err(e) =
+                            send(&mut socket_tx, M::new_lag(n), encoding, public_fields).await
+                        {
                             warn!(target: "websockets", "Failed to send Lagged message: {e}");
                             break;
                         }
@@ -276,64 +1303,160 @@ pub async fn handle_ws<M: ApiMessage + 'static>(
         });
     });
 
-    while socket_rx.next().await.is_some() {}
+    // Pings are answered by axum, pongs are what we are waiting for.
+    #[allow(clippy::redundant_pub_crate)]
+    loop {
+        tokio::select! {
+            msg = socket_rx.next() => match msg {
+                Some(Ok(Message::Pong(_))) => pong_received.notify_one(),
+                Some(_) => {}
+                None => break,
+            },
+            // Don't wait for a client that stopped answering to close its side.
+            () = timed_out.notified() => break,
+        }
+    }
     handle.abort();
     info!(target: "websockets", "Websocket closed");
 }
 
 pub struct AppState {
     checkers: BTreeMapWithSender<CheckerWithSender>,
+    /// How the histories of new checkers are kept
+    history_backend: HistoryBackend,
     history_len: usize,
+    /// Age beyond which statuses are dropped, on top of the length limit
+    history_max_age: Option<chrono::Duration>,
+    damping: Damping,
+    /// Capacity of the broadcast channels of checkers whose spec doesn't set one, and of the list
+    /// of checkers
+    channel_capacity: usize,
+    global_sender: tokio::sync::broadcast::Sender<GlobalMessage>,
+    transition_sender: tokio::sync::broadcast::Sender<TransitionMessage>,
+    shutdown: Arc<watch::Sender<bool>>,
+    /// When dumps started failing and the error of the last one, if the last dump failed
+    dump_failure: Option<(DateTime<Local>, String)>,
+    started: std::time::Instant,
+    /// Where changes to histories are sent to be saved as they happen, if the persistence backend
+    /// records them
+    history_changes: Option<mpsc::UnboundedSender<HistoryChange>>,
 }
 
 impl AppState {
     pub fn new(
-        checkers: BTreeMap<String, checker::Checker<StatusRingBuffer>>,
+        checkers: BTreeMap<String, checker::Checker<History>>,
+        history_backend: HistoryBackend,
         history_len: usize,
+        history_max_age: Option<chrono::Duration>,
+        damping: Damping,
+        channel_capacity: usize,
+        global_channel_capacity: usize,
     ) -> Self {
+        // Broadcast channels panic with a capacity of 0.
+        let channel_capacity = channel_capacity.max(1);
+        let global_channel_capacity = global_channel_capacity.max(1);
+        let global_sender = tokio::sync::broadcast::channel(global_channel_capacity).0;
+        let transition_sender = tokio::sync::broadcast::channel(global_channel_capacity).0;
+        let checkers = checkers
+            .into_iter()
+            .map(|(k, v)| {
+                let checker = CheckerWithSender::new(
+                    k.clone(),
+                    v,
+                    history_max_age,
+                    damping,
+                    channel_capacity,
+                    global_sender.clone(),
+                    transition_sender.clone(),
+                );
+                (k, checker)
+            })
+            .collect();
         Self {
-            checkers: checkers
-                .into_iter()
-                .map(|(k, v)| (k, CheckerWithSender::new(v)))
-                .collect::<BTreeMap<String, CheckerWithSender>>()
-                .into(),
+            checkers: BTreeMapWithSender::new(checkers, channel_capacity, global_sender.clone()),
+            history_backend,
             history_len,
+            history_max_age,
+            damping,
+            channel_capacity,
+            global_sender,
+            transition_sender,
+            shutdown: Arc::new(watch::channel(false).0),
+            dump_failure: None,
+            started: std::time::Instant::now(),
+            history_changes: None,
         }
     }
 
+    /// Send changes to the histories of all checkers, including those created later, to be
+    /// saved as they happen.
+    pub fn record_history_changes(&mut self, sender: mpsc::UnboundedSender<HistoryChange>) {
+        for checker in self.checkers.values_mut() {
+            checker.record_history_changes(sender.clone());
+        }
+        self.history_changes = Some(sender);
+    }
+
     pub fn add_checker(
         &mut self,
         name: String,
         checker_spec: checker::Spec,
     ) -> Result<(), CheckerAlreadyExists> {
         if self.checkers.inner().contains_key(&name) {
-            return Err(CheckerAlreadyExists);
+            return Err(CheckerAlreadyExists(name));
         }
-        self.checkers.insert(
-            name,
-            CheckerWithSender::new(checker::Checker::new(
-                checker_spec,
-                StatusRingBuffer::new(self.history_len),
-            )),
+        let history_len = self.history_len_of(&checker_spec);
+        let mut checker = CheckerWithSender::new(
+            name.clone(),
+            checker::Checker::new(
+                checker_spec.clone(),
+                self.history_backend.new_history(history_len),
+            ),
+            self.history_max_age,
+            self.damping,
+            self.channel_capacity,
+            self.global_sender.clone(),
+            self.transition_sender.clone(),
         );
+        if let Some(sender) = &self.history_changes {
+            checker.record_history_changes(sender.clone());
+        }
+        self.checkers.insert(name.clone(), checker);
+        // Those watching all checkers only got the name with the insertion.
+        let msg = GlobalMessage::Checker {
+            name,
+            message: CheckerMessage::UpdatedSpec { spec: checker_spec },
+        };
+        if let Err(e) = self.global_sender.send(msg) {
+            debug!(target: "websockets", "Failed to send spec of new checker: {e}, ignoring.");
+        }
         Ok(())
     }
 
+    /// The number of statuses kept for a checker with the given spec.
+    pub fn history_len_of(&self, spec: &checker::Spec) -> usize {
+        spec.history_len_or(self.history_len)
+    }
+
     pub fn remove_checker(
         &mut self,
         name: &str,
-    ) -> Result<checker::Checker<StatusRingBuffer>, CheckerDoesNotExist> {
+    ) -> Result<checker::Checker<History>, CheckerDoesNotExist> {
         // The websockets will be gracefully closed when the CheckerWithSender is dropped.
-        self.checkers
+        let removed = self
+            .checkers
             .remove(name)
-            .map(|w| w.checker().clone())
-            .ok_or(CheckerDoesNotExist)
+            .ok_or_else(|| CheckerDoesNotExist(name.to_string()))?;
+        removed.record(HistoryChange::Removed {
+            checker: name.to_string(),
+        });
+        Ok(removed.checker().clone())
     }
 
     pub fn get_checker(
         &self,
         name: &str,
-    ) -> Result<checker::Checker<StatusRingBuffer>, CheckerDoesNotExist> {
+    ) -> Result<checker::Checker<History>, CheckerDoesNotExist> {
         self.get_checker_with_sender(name)
             .map(|w| w.checker().clone())
     }
@@ -342,17 +1465,54 @@ impl AppState {
         &self,
         name: &str,
     ) -> Result<&CheckerWithSender, CheckerDoesNotExist> {
-        self.checkers.inner().get(name).ok_or(CheckerDoesNotExist)
+        self.checkers
+            .inner()
+            .get(name)
+            .ok_or_else(|| CheckerDoesNotExist(name.to_string()))
     }
 
     pub fn get_checker_with_sender_mut(
         &mut self,
         name: &str,
     ) -> Result<&mut CheckerWithSender, CheckerDoesNotExist> {
-        self.checkers.get_mut(name).ok_or(CheckerDoesNotExist)
+        self.checkers
+            .get_mut(name)
+            .ok_or_else(|| CheckerDoesNotExist(name.to_string()))
     }
 
-    pub fn get_checkers(&self) -> BTreeMap<String, checker::Checker<StatusRingBuffer>> {
+    pub fn checker_count(&self) -> usize {
+        self.checkers.inner().len()
+    }
+
+    /// Time since the state was created, i.e. since the server started.
+    pub fn uptime(&self) -> std::time::Duration {
+        self.started.elapsed()
+    }
+
+    /// Iterate over the checkers without cloning them, unlike `get_checkers`.
+    pub fn iter_checkers(&self) -> impl Iterator<Item = (&String, &checker::Checker<History>)> {
+        self.checkers
+            .inner()
+            .iter()
+            .map(|(name, w)| (name, w.checker()))
+    }
+
+    /// Map each tag to the names of the checkers with it, in order.
+    pub fn get_tags(&self) -> BTreeMap<String, Vec<String>> {
+        let mut tags = BTreeMap::<String, Vec<String>>::new();
+        for (name, checker) in self.iter_checkers() {
+            for tag in &checker.spec.tags {
+                let names = tags.entry(tag.clone()).or_default();
+                // Checkers are iterated in order, so a tag given twice is the last name.
+                if names.last() != Some(name) {
+                    names.push(name.clone());
+                }
+            }
+        }
+        tags
+    }
+
+    pub fn get_checkers(&self) -> BTreeMap<String, checker::Checker<History>> {
         self.checkers
             .inner()
             .iter()
@@ -360,39 +1520,177 @@ impl AppState {
             .collect()
     }
 
-    pub fn checkers_to_json(&self) -> Result<String, serde_json::Error> {
-        let checkers: BTreeMap<String, checker::Checker<StatusRingBuffer>> = self
+    /// Get everything to save about the checkers but their statuses, which is cheaper than
+    /// `get_checkers` for persistence backends that record statuses as they are added.
+    pub fn get_saved_specs(&self) -> BTreeMap<String, SavedSpec> {
+        self.checkers
+            .inner()
+            .iter()
+            .map(|(k, v)| {
+                let statuses = &v.checker().statuses;
+                let saved = SavedSpec {
+                    spec: v.checker().spec.clone(),
+                    capacity: statuses.capacity(),
+                    head: statuses.head_index(),
+                    oldest: statuses.iter().next().map(|(time, _)| time),
+                };
+                (k.clone(), saved)
+            })
+            .collect()
+    }
+
+    /// Get notified when the server shuts down, so that websockets can be closed gracefully.
+    pub fn subscribe_shutdown(&self) -> watch::Receiver<bool> {
+        self.shutdown.subscribe()
+    }
+
+    /// Tell the websockets the server is shutting down.
+    /// Returns the sender, whose `closed` method resolves once all websockets are closed.
+    pub fn shut_down(&self) -> Arc<watch::Sender<bool>> {
+        self.shutdown.send_replace(true);
+        self.shutdown.clone()
+    }
+
+    /// Subscribe to the events of the list and of all checkers, along with the initial message
+    /// describing their current state.
+    pub fn subscribe_global(
+        &self,
+    ) -> (
+        tokio::sync::broadcast::Receiver<GlobalMessage>,
+        GlobalMessage,
+    ) {
+        let initial = self
             .checkers
             .inner()
             .iter()
-            .map(|(k, v)| (k.clone(), v.checker().clone()))
+            .map(|(k, v)| {
+                let checker = v.checker();
+                (
+                    k.clone(),
+                    (
+                        checker.spec.clone(),
+                        checker.statuses.latest_with_state_since(),
+                    ),
+                )
+            })
             .collect();
-        serde_json::to_string(&checkers)
+        (
+            self.global_sender.subscribe(),
+            GlobalMessage::Initial { checkers: initial },
+        )
+    }
+
+    /// Record the outcome of a dump of the checkers.
+    pub fn record_dump(&mut self, result: Result<(), String>) {
+        match result {
+            Ok(()) => self.dump_failure = None,
+            Err(error) => {
+                let since = self
+                    .dump_failure
+                    .take()
+                    .map_or_else(Local::now, |(since, _)| since);
+                self.dump_failure = Some((since, error));
+            }
+        }
+    }
+
+    /// Get when dumps started failing and the error of the last one, if the last dump failed.
+    pub const fn dump_failure(&self) -> Option<&(DateTime<Local>, String)> {
+        self.dump_failure.as_ref()
+    }
+
+    /// Subscribe to the transitions of all checkers, and get their current states.
+    pub fn subscribe_transitions(
+        &self,
+    ) -> (
+        tokio::sync::broadcast::Receiver<TransitionMessage>,
+        TransitionMessage,
+    ) {
+        let states = self
+            .checkers
+            .inner()
+            .iter()
+            .map(|(name, w)| (name.clone(), w.is_up()))
+            .collect();
+        (
+            self.transition_sender.subscribe(),
+            TransitionMessage::Initial { states },
+        )
+    }
+
+    /// Warn about checkers whose subscribers are falling behind, before they start lagging and
+    /// missing messages.
+    pub fn warn_about_backlogs(&self) {
+        for (name, checker) in self.checkers.inner() {
+            let (len, capacity) = checker.backlog();
+            // Over 75% of the capacity
+            if len * 4 > capacity * 3 {
+                warn!(
+                    target: "websockets",
+                    checker = name,
+                    "Subscribers of {name} are falling behind: {len} of {capacity} messages queued"
+                );
+            }
+        }
+    }
+
+    /// Get the status of every group of checkers.
+    pub fn get_groups(&self, policy: groups::RollupPolicy) -> BTreeMap<String, GroupStatus> {
+        groups::roll_up(
+            self.checkers.inner().iter().map(|(name, w)| {
+                let checker = w.checker();
+                (
+                    name,
+                    &checker.spec,
+                    checker
+                        .statuses
+                        .iter()
+                        .next_back()
+                        .map(|(_, status)| status),
+                )
+            }),
+            policy,
+        )
+    }
+
+    /// Get the latest status of each checker, or `None` for checkers without any status yet.
+    pub fn get_latest_statuses(&self) -> BTreeMap<String, Option<LatestStatus>> {
+        self.checkers
+            .inner()
+            .iter()
+            .map(|(k, v)| (k.clone(), v.checker().statuses.latest_with_state_since()))
+            .collect()
     }
 }
 
 #[derive(Debug)]
-pub struct CheckerAlreadyExists;
+pub struct CheckerAlreadyExists(pub String);
 #[derive(Debug)]
-pub struct CheckerDoesNotExist;
+pub struct CheckerDoesNotExist(pub String);
 
 mod btreemap_with_sender {
     use std::collections::{btree_map, BTreeMap};
-    use swec_core::ListMessage;
+    use swec_core::{GlobalMessage, ListMessage};
+    use tokio::sync::broadcast;
     use tracing::warn;
 
     #[derive(Debug)]
     pub struct BTreeMapWithSender<T> {
         btreemap: BTreeMap<String, T>,
-        sender: tokio::sync::broadcast::Sender<ListMessage>,
+        sender: broadcast::Sender<ListMessage>,
+        global_sender: broadcast::Sender<GlobalMessage>,
     }
 
     impl<T> BTreeMapWithSender<T> {
-        #[must_use]
-        pub fn new() -> Self {
+        pub fn new(
+            btreemap: BTreeMap<String, T>,
+            channel_capacity: usize,
+            global_sender: broadcast::Sender<GlobalMessage>,
+        ) -> Self {
             Self {
-                btreemap: BTreeMap::new(),
-                sender: tokio::sync::broadcast::channel(16).0,
+                btreemap,
+                sender: broadcast::channel(channel_capacity).0,
+                global_sender,
             }
         }
 
@@ -412,13 +1710,17 @@ mod btreemap_with_sender {
             self.btreemap.get_mut(key)
         }
 
+        pub fn values_mut(&mut self) -> btree_map::ValuesMut<'_, String, T> {
+            self.btreemap.values_mut()
+        }
+
         pub fn insert(&mut self, key: String, value: T) -> Option<T> {
             let r = self.btreemap.insert(key.clone(), value);
             let msg = match r {
-                Some(_) => ListMessage::InsertReplace(key),
-                None => ListMessage::Insert(key),
+                Some(_) => ListMessage::InsertReplace { name: key },
+                None => ListMessage::Insert { name: key },
             };
-            if let Err(e) = self.sender.send(msg) {
+            if let Err(e) = self.send(msg) {
                 warn!(target: "websockets", "Failed to send msg: {e}, ignoring.");
             }
             r
@@ -427,7 +1729,9 @@ mod btreemap_with_sender {
         pub fn remove(&mut self, key: &str) -> Option<T> {
             match self.btreemap.remove(key) {
                 Some(v) => {
-                    if let Err(e) = self.sender.send(ListMessage::Remove(key.to_string())) {
+                    if let Err(e) = self.send(ListMessage::Remove {
+                        name: key.to_string(),
+                    }) {
                         warn!(target: "websockets", "Failed to send Remove: {e}, ignoring.");
                     }
                     Some(v)
@@ -435,65 +1739,352 @@ mod btreemap_with_sender {
                 None => None,
             }
         }
-    }
 
-    impl<T> From<BTreeMap<String, T>> for BTreeMapWithSender<T> {
-        fn from(btreemap: BTreeMap<String, T>) -> Self {
-            Self {
-                btreemap,
-                sender: tokio::sync::broadcast::channel(16).0,
-            }
+        /// Send a message to those watching the list and those watching all checkers.
+        fn send(
+            &self,
+            msg: ListMessage,
+        ) -> Result<usize, broadcast::error::SendError<ListMessage>> {
+            // Nobody watching all checkers is the usual case, not worth logging.
+            let _ = self.global_sender.send(GlobalMessage::List {
+                message: msg.clone(),
+            });
+            self.sender.send(msg)
         }
     }
 }
 
 mod checker_with_sender {
-    use super::StatusRingBuffer;
-    use chrono::Local;
+    use super::History;
+    use crate::history::{OutOfOrderPolicy, OutOfOrderStatus};
+    use crate::persistence::HistoryChange;
+    use chrono::{DateTime, Local};
     use swec_core::checker;
-    use swec_core::CheckerMessage;
+    use swec_core::{CheckerMessage, GlobalMessage, TransitionMessage};
+    use tokio::sync::{broadcast, mpsc};
     use tracing::{debug, warn};
 
+    /// Maximum capacity a spec can set, so that a single checker can't use too much memory.
+    const MAX_CHANNEL_CAPACITY: usize = 1024;
+
+    /// How long a checker must stay in a new state before the transition is reported, so that a
+    /// single blip doesn't fire a notification. A transition is confirmed as soon as either
+    /// threshold is reached, or immediately if neither is set.
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct Damping {
+        /// Number of consecutive statuses in the new state
+        pub statuses: Option<usize>,
+        /// Time spent in the new state
+        pub duration: Option<std::time::Duration>,
+    }
+
+    impl Damping {
+        fn is_confirmed(&self, pending: &PendingTransition, time: DateTime<Local>) -> bool {
+            match (self.statuses, self.duration) {
+                (None, None) => true,
+                (statuses, duration) => {
+                    statuses.is_some_and(|n| pending.statuses >= n)
+                        || duration.is_some_and(|d| {
+                            (time - pending.since)
+                                .to_std()
+                                .is_ok_and(|elapsed| elapsed >= d)
+                        })
+                }
+            }
+        }
+    }
+
+    /// A change of state that isn't confirmed yet.
+    #[derive(Debug)]
+    struct PendingTransition {
+        since: DateTime<Local>,
+        statuses: usize,
+    }
+
     #[derive(Debug)]
     /// Encapsulates a `checker::Checker` with a `tokio::sync::broadcast::Sender` to send updates
     /// to subscribers. This needs to be in a separate module for the privacy of the inner fields
     /// (so that we don't modify a checker without sending an update).
     pub struct CheckerWithSender {
-        checker: checker::Checker<StatusRingBuffer>,
-        sender: tokio::sync::broadcast::Sender<CheckerMessage>,
+        name: String,
+        checker: checker::Checker<History>,
+        sender: broadcast::Sender<CheckerMessage>,
+        global_sender: broadcast::Sender<GlobalMessage>,
+        /// Transitions of this checker only
+        transition_sender: broadcast::Sender<TransitionMessage>,
+        /// Transitions of all checkers
+        global_transition_sender: broadcast::Sender<TransitionMessage>,
+        /// Age beyond which statuses are dropped when a status is added
+        max_age: Option<chrono::Duration>,
+        damping: Damping,
+        /// Capacity of `sender` and `transition_sender`
+        channel_capacity: usize,
+        /// The last confirmed state, or `None` if there is no status yet
+        is_up: Option<bool>,
+        pending_transition: Option<PendingTransition>,
+        /// End of the maintenance window, during which statuses are flagged. This isn't dumped,
+        /// so restarting the server ends it.
+        maintenance_until: Option<DateTime<Local>>,
+        /// Where changes to the history are sent to be saved as they happen, if the persistence
+        /// backend records them
+        history_changes: Option<mpsc::UnboundedSender<HistoryChange>>,
     }
 
     impl CheckerWithSender {
-        pub fn new(checker: checker::Checker<StatusRingBuffer>) -> Self {
-            let (sender, _) = tokio::sync::broadcast::channel(16);
-            Self { checker, sender }
+        pub fn new(
+            name: String,
+            checker: checker::Checker<History>,
+            max_age: Option<chrono::Duration>,
+            damping: Damping,
+            default_channel_capacity: usize,
+            global_sender: broadcast::Sender<GlobalMessage>,
+            global_transition_sender: broadcast::Sender<TransitionMessage>,
+        ) -> Self {
+            // Channels can't be resized, so this only follows the spec at creation.
+            let channel_capacity = checker
+                .spec
+                .channel_capacity
+                .map_or(default_channel_capacity, |n| {
+                    n.clamp(1, MAX_CHANNEL_CAPACITY)
+                });
+            let (sender, _) = broadcast::channel(channel_capacity);
+            let (transition_sender, _) = broadcast::channel(channel_capacity);
+            let is_up = checker
+                .statuses
+                .iter()
+                .next_back()
+                .map(|(_, status)| status.is_up());
+            Self {
+                name,
+                checker,
+                sender,
+                global_sender,
+                transition_sender,
+                global_transition_sender,
+                max_age,
+                damping,
+                channel_capacity,
+                is_up,
+                pending_transition: None,
+                maintenance_until: None,
+                history_changes: None,
+            }
+        }
+
+        /// Send changes to the history to be saved as they happen.
+        pub fn record_history_changes(&mut self, sender: mpsc::UnboundedSender<HistoryChange>) {
+            self.history_changes = Some(sender);
+        }
+
+        /// Send a change to the history to be saved, if changes are recorded.
+        pub fn record(&self, change: HistoryChange) {
+            if let Some(sender) = &self.history_changes {
+                if sender.send(change).is_err() {
+                    warn!("Failed to record a change to the history of {}", self.name);
+                }
+            }
         }
 
-        pub const fn checker(&self) -> &checker::Checker<StatusRingBuffer> {
+        pub const fn checker(&self) -> &checker::Checker<History> {
             &self.checker
         }
 
-        pub fn subscribe(&self) -> tokio::sync::broadcast::Receiver<CheckerMessage> {
+        pub fn subscribe(&self) -> broadcast::Receiver<CheckerMessage> {
             self.sender.subscribe()
         }
 
-        pub fn update_spec(&mut self, spec: checker::Spec) {
+        pub fn subscribe_transitions(&self) -> broadcast::Receiver<TransitionMessage> {
+            self.transition_sender.subscribe()
+        }
+
+        /// Get the last confirmed state of the checker, or `None` if it has no status yet.
+        pub const fn is_up(&self) -> Option<bool> {
+            self.is_up
+        }
+
+        /// Get the number of messages not yet received by all subscribers, and the number of
+        /// messages after which the slowest subscribers start lagging.
+        pub fn backlog(&self) -> (usize, usize) {
+            (self.sender.len(), self.channel_capacity)
+        }
+
+        /// Send a message to those watching this checker and those watching all checkers.
+        fn send(&self, msg: CheckerMessage) -> Result<usize, broadcast::error::SendError<()>> {
+            // Nobody watching all checkers is the usual case, not worth logging.
+            let _ = self.global_sender.send(GlobalMessage::Checker {
+                name: self.name.clone(),
+                message: msg.clone(),
+            });
+            // The message is dropped rather than returned, as it is too large for a `Result`.
+            self.sender
+                .send(msg)
+                .map_err(|_| broadcast::error::SendError(()))
+        }
+
+        /// Replace the spec, resizing the history to the given length, which should follow the
+        /// history length of the spec, if needed.
+        pub fn update_spec(&mut self, spec: checker::Spec, history_len: usize) {
+            if self.checker.statuses.capacity() != history_len {
+                self.checker.statuses.truncate_fifo(history_len);
+            }
             self.checker.spec = spec.clone();
-            if let Err(e) = self.sender.send(CheckerMessage::UpdatedSpec(spec)) {
+            if let Err(e) = self.send(CheckerMessage::UpdatedSpec { spec }) {
                 warn!(target: "websockets", "Failed to send updated spec: {e}, ignoring.");
             }
         }
 
-        pub fn add_status(&mut self, status: checker::Status) {
-            let time = Local::now();
-            self.checker.statuses.push((time, status.clone()));
-            if let Err(e) = self.sender.send(CheckerMessage::AddedStatus(time, status)) {
+        /// Put the checker in maintenance until the given time, or take it out of maintenance.
+        pub fn set_maintenance(&mut self, until: Option<DateTime<Local>>) {
+            self.maintenance_until = until;
+            if let Err(e) = self.send(CheckerMessage::MaintenanceChanged { until }) {
+                debug!(target: "websockets", "Failed to send maintenance change: {e}, ignoring.");
+            }
+        }
+
+        /// Remove all statuses, keeping the spec and the subscribers, who are told to reset.
+        /// The next status is then treated like the first one, which is not a transition.
+        pub fn clear_statuses(&mut self) {
+            self.checker.statuses.clear();
+            self.record(HistoryChange::Cleared {
+                checker: self.name.clone(),
+            });
+            self.is_up = None;
+            self.pending_transition = None;
+            if let Err(e) = self.send(CheckerMessage::HistoryCleared) {
+                debug!(target: "websockets", "Failed to send cleared history: {e}, ignoring.");
+            }
+        }
+
+        pub fn add_status(
+            &mut self,
+            status: checker::Status,
+            policy: OutOfOrderPolicy,
+        ) -> Result<(), OutOfOrderStatus> {
+            self.add_status_at(Local::now(), status, policy)
+        }
+
+        /// Add a status observed at the given time, e.g. by a checker that posts in batches.
+        /// Only statuses that end up the newest one are used to detect transitions.
+        pub fn add_status_at(
+            &mut self,
+            time: DateTime<Local>,
+            status: checker::Status,
+            policy: OutOfOrderPolicy,
+        ) -> Result<(), OutOfOrderStatus> {
+            let status = if self.maintenance_until.is_some_and(|until| time < until) {
+                status.with_maintenance(true)
+            } else {
+                status
+            };
+            // Statuses placed in the past by the policy don't say what state the checker is in now.
+            let is_newest = self
+                .checker
+                .statuses
+                .newest_time()
+                .is_none_or(|newest| time >= newest);
+            self.checker
+                .statuses
+                .push_status((time, status.clone()), policy)?;
+            self.record(HistoryChange::Added {
+                checker: self.name.clone(),
+                status: (time, status.clone()),
+                head: self.checker.statuses.head_index(),
+            });
+            // Evicting here rather than in a background task means idle checkers keep their
+            // old statuses until they get a new one, which is fine: they aren't growing.
+            if let Some(max_age) = self.max_age {
+                self.checker.statuses.evict_older_than(max_age);
+            }
+            let is_up = status.is_up();
+            if let Err(e) = self.send(CheckerMessage::AddedStatus { time, status }) {
                 debug!(target: "websockets", "Failed to send added status: {e}, ignoring.");
             }
+            if let Some(since) = is_newest
+                .then(|| self.track_transition(time, is_up))
+                .flatten()
+            {
+                if let Err(e) = self.send(CheckerMessage::Transition { since, is_up }) {
+                    debug!(target: "websockets", "Failed to send transition: {e}, ignoring.");
+                }
+                let msg = TransitionMessage::Transition {
+                    name: self.name.clone(),
+                    since,
+                    is_up,
+                };
+                // Like for the other messages, having no subscribers is not an error.
+                let _ = self.global_transition_sender.send(msg.clone());
+                let _ = self.transition_sender.send(msg);
+            }
+            Ok(())
+        }
+
+        /// Follow the state of the checker as statuses are added.
+        /// Returns the time the checker entered its new state when a transition is confirmed.
+        fn track_transition(
+            &mut self,
+            time: DateTime<Local>,
+            is_up: bool,
+        ) -> Option<DateTime<Local>> {
+            match self.is_up {
+                None => {
+                    // The first status is not a transition.
+                    self.is_up = Some(is_up);
+                    None
+                }
+                Some(confirmed) if confirmed == is_up => {
+                    // Back to the confirmed state before the transition was confirmed, if any.
+                    self.pending_transition = None;
+                    None
+                }
+                Some(_) => {
+                    let pending = self.pending_transition.get_or_insert(PendingTransition {
+                        since: time,
+                        statuses: 0,
+                    });
+                    pending.statuses += 1;
+                    if self.damping.is_confirmed(pending, time) {
+                        let since = pending.since;
+                        self.is_up = Some(is_up);
+                        self.pending_transition = None;
+                        Some(since)
+                    } else {
+                        None
+                    }
+                }
+            }
+        }
+
+        /// Add statuses observed at the given times, oldest first.
+        /// Under `OutOfOrderPolicy::Reject`, either all of them are added or, if one of them would
+        /// be rejected, none are. `OutOfOrderPolicy::Insert` adds them one by one, so if one of
+        /// them is too old to be kept by a full history, those before it stay added and the
+        /// error is returned for it.
+        pub fn add_statuses_at(
+            &mut self,
+            statuses: &[(DateTime<Local>, checker::Status)],
+            policy: OutOfOrderPolicy,
+        ) -> Result<(), OutOfOrderStatus> {
+            if policy == OutOfOrderPolicy::Reject {
+                let mut newest = self.checker.statuses.newest_time();
+                for (time, _) in statuses {
+                    if let Some(newest) = newest.filter(|newest| time < newest) {
+                        return Err(OutOfOrderStatus {
+                            time: *time,
+                            newest,
+                        });
+                    }
+                    newest = Some(*time);
+                }
+            }
+            for (time, status) in statuses {
+                self.add_status_at(*time, status.clone(), policy)?;
+            }
+            Ok(())
         }
     }
 
     impl Drop for CheckerWithSender {
+        // Not sent to those watching all checkers, who get a `ListMessage::Remove` instead.
         fn drop(&mut self) {
             if let Err(e) = self.sender.send(CheckerMessage::CheckerDropped) {
                 warn!(target: "websockets", "Failed to send CheckerDropped: {e}, ignoring.");
@@ -501,3 +2092,445 @@ mod checker_with_sender {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::OutOfOrderPolicy;
+    use auth::Identity;
+
+    fn state() -> ApiState {
+        state_with(true, Config::default())
+    }
+
+    fn state_with(writable: bool, config: Config) -> ApiState {
+        let api_info = ApiInfo {
+            writable,
+            swec_version: crate::VERSION.to_string(),
+        };
+        let app_state = AppState::new(
+            BTreeMap::new(),
+            config.history_backend,
+            10,
+            None,
+            Damping::default(),
+            16,
+            64,
+        );
+        (api_info, Arc::new(config), Arc::new(RwLock::new(app_state)))
+    }
+
+    fn team_a() -> Requester {
+        Requester {
+            address: None,
+            identity: Identity::Prefixed("team-a/".to_string()),
+        }
+    }
+
+    fn spec() -> checker::Spec {
+        checker::Spec::new("Service".to_string(), None, None)
+    }
+
+    /// Add a checker keeping `history_len` statuses, and post `count` statuses to it, with their
+    /// number as message.
+    async fn add_checker_with_statuses(
+        state: &ApiState,
+        name: &str,
+        history_len: usize,
+        count: usize,
+    ) {
+        let spec = checker::Spec {
+            history_len: Some(history_len),
+            ..spec()
+        };
+        let mut app_state = state.2.write().await;
+        app_state.add_checker(name.to_string(), spec).unwrap();
+        push_statuses(&mut app_state, name, 0..count);
+    }
+
+    fn push_statuses(app_state: &mut AppState, name: &str, numbers: std::ops::Range<usize>) {
+        let checker = app_state.get_checker_with_sender_mut(name).unwrap();
+        for n in numbers {
+            checker
+                .add_status(checker::Status::up(n.to_string()), OutOfOrderPolicy::Reject)
+                .unwrap();
+        }
+    }
+
+    async fn body<T: serde::de::DeserializeOwned>(response: Response) -> T {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    fn messages(range: &StatusRange) -> Vec<String> {
+        range
+            .statuses
+            .iter()
+            .map(|(_, status)| status.message.clone())
+            .collect()
+    }
+
+    fn statuses_query(since_index: Option<u64>) -> Query<StatusesQuery> {
+        Query(StatusesQuery {
+            since: None,
+            until: None,
+            limit: None,
+            offset: None,
+            since_index,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_prefixed_token_allowed_name() {
+        let state = state();
+        let (status, _) = post_checker_spec(
+            State(state.clone()),
+            Path("team-a/web".to_string()),
+            team_a(),
+            Json(spec()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::CREATED);
+        let (status, _) = put_checker_spec(
+            State(state.clone()),
+            Path("team-a/web".to_string()),
+            team_a(),
+            Json(spec()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::OK);
+        delete_checker(
+            State(state.clone()),
+            Path("team-a/web".to_string()),
+            team_a(),
+        )
+        .await
+        .unwrap();
+        assert!(state.2.read().await.checkers.inner().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_prefixed_token_refused_name() {
+        let state = state();
+        let refused = |e: ApiError| e.into_response().status() == StatusCode::FORBIDDEN;
+        let error = post_checker_spec(
+            State(state.clone()),
+            Path("team-b/web".to_string()),
+            team_a(),
+            Json(spec()),
+        )
+        .await
+        .unwrap_err();
+        assert!(refused(error));
+        let error = put_checker_spec(
+            State(state.clone()),
+            Path("team-b/web".to_string()),
+            team_a(),
+            Json(spec()),
+        )
+        .await
+        .unwrap_err();
+        assert!(refused(error));
+        assert!(state.2.read().await.checkers.inner().is_empty());
+
+        // Checkers created with another token can't be deleted either.
+        state
+            .2
+            .write()
+            .await
+            .add_checker("team-b/web".to_string(), spec())
+            .unwrap();
+        let error = delete_checker(
+            State(state.clone()),
+            Path("team-b/web".to_string()),
+            team_a(),
+        )
+        .await
+        .unwrap_err();
+        assert!(refused(error));
+        assert_eq!(state.2.read().await.checkers.inner().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_prefixed_token_refused_changes() {
+        let state = state();
+        state
+            .2
+            .write()
+            .await
+            .add_checker("team-b/web".to_string(), spec())
+            .unwrap();
+        state
+            .2
+            .write()
+            .await
+            .get_checker_with_sender_mut("team-b/web")
+            .unwrap()
+            .add_status(checker::Status::up("Up"), OutOfOrderPolicy::Reject)
+            .unwrap();
+        let name = || Path("team-b/web".to_string());
+        let refused = |e: ApiError| e.into_response().status() == StatusCode::FORBIDDEN;
+
+        let error = delete_checker_statuses(State(state.clone()), name(), team_a())
+            .await
+            .unwrap_err();
+        assert!(refused(error));
+        let maintenance = Maintenance {
+            until: Some(Local::now() + chrono::Duration::try_hours(1).unwrap()),
+        };
+        let error =
+            post_checker_maintenance(State(state.clone()), name(), team_a(), Json(maintenance))
+                .await
+                .unwrap_err();
+        assert!(refused(error));
+        let error = post_checker_disable(State(state.clone()), name(), team_a())
+            .await
+            .unwrap_err();
+        assert!(refused(error));
+        let error = post_checker_enable(State(state.clone()), name(), team_a())
+            .await
+            .unwrap_err();
+        assert!(refused(error));
+        let status = PostedStatuses::One(PostedStatus::Untimed(checker::Status::down("Down")));
+        let error = post_checker_status(State(state.clone()), name(), team_a(), Json(status))
+            .await
+            .unwrap_err();
+        assert!(refused(error));
+        let statuses = vec![(Local::now(), checker::Status::down("Down"))];
+        let error = post_checker_statuses(State(state.clone()), name(), team_a(), Json(statuses))
+            .await
+            .unwrap_err();
+        assert!(refused(error));
+
+        // Nothing changed.
+        let app_state = state.2.read().await;
+        let checker = app_state.get_checker_with_sender("team-b/web").unwrap();
+        assert!(checker.checker().spec.enabled);
+        assert_eq!(checker.checker().statuses.len(), 1);
+        assert_eq!(checker.is_up(), Some(true));
+    }
+
+    #[tokio::test]
+    async fn test_prefixed_token_bulk_creation() {
+        let state = state();
+        let specs = BTreeMap::from([
+            ("team-a/web".to_string(), spec()),
+            ("team-b/web".to_string(), spec()),
+        ]);
+        let Json(results) = post_checkers(
+            State(state.clone()),
+            Query(PostCheckersQuery { atomic: false }),
+            team_a(),
+            Json(specs.clone()),
+        )
+        .await
+        .unwrap();
+        assert!(matches!(results["team-a/web"], CreationResult::Created));
+        assert!(matches!(results["team-b/web"], CreationResult::Forbidden));
+
+        let state = self::state();
+        post_checkers(
+            State(state.clone()),
+            Query(PostCheckersQuery { atomic: true }),
+            team_a(),
+            Json(specs),
+        )
+        .await
+        .unwrap_err();
+        assert!(state.2.read().await.checkers.inner().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_groups_hidden_from_public_api() {
+        let config = Config {
+            public_spec_fields: PublicSpecFields {
+                url: true,
+                group: false,
+            },
+            ..Config::default()
+        };
+        let grouped = checker::Spec::new("Service".to_string(), None, Some("web".to_string()));
+        let public = state_with(false, config.clone());
+        let private = state_with(true, config);
+        for state in [&public, &private] {
+            state
+                .2
+                .write()
+                .await
+                .add_checker("a".to_string(), grouped.clone())
+                .unwrap();
+        }
+
+        let not_found = |e: ApiError| e.into_response().status() == StatusCode::NOT_FOUND;
+        assert!(not_found(
+            get_groups(State(public.clone())).await.unwrap_err()
+        ));
+        let error = get_group(State(public), Path("web".to_string()))
+            .await
+            .unwrap_err();
+        assert!(not_found(error));
+
+        let Json(groups) = get_groups(State(private.clone())).await.unwrap();
+        assert!(groups.contains_key("web"));
+        get_group(State(private), Path("web".to_string()))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_statuses_since_index() {
+        let state = state();
+        add_checker_with_statuses(&state, "a", 5, 3).await;
+        let get = |since_index| {
+            get_checker_statuses(
+                State(state.clone()),
+                Path("a".to_string()),
+                statuses_query(since_index),
+            )
+        };
+
+        let first: StatusRange = body(get(None).await.unwrap()).await;
+        assert_eq!(messages(&first), ["0", "1", "2"]);
+        assert_eq!(first.head, 3);
+
+        // Resuming from the head gives exactly the statuses added since.
+        push_statuses(&mut *state.2.write().await, "a", 3..5);
+        let next: StatusRange = body(get(Some(first.head)).await.unwrap()).await;
+        assert_eq!(messages(&next), ["3", "4"]);
+        assert_eq!(next.head, 5);
+        let none: StatusRange = body(get(Some(next.head)).await.unwrap()).await;
+        assert!(none.statuses.is_empty());
+        assert_eq!(none.head, 5);
+
+        // Past the capacity, a stale index gets what is left of what was added since, once.
+        push_statuses(&mut *state.2.write().await, "a", 5..12);
+        let stale: StatusRange = body(get(Some(next.head)).await.unwrap()).await;
+        assert_eq!(messages(&stale), ["7", "8", "9", "10", "11"]);
+        assert_eq!(stale.head, 12);
+        push_statuses(&mut *state.2.write().await, "a", 12..13);
+        let after: StatusRange = body(get(Some(stale.head)).await.unwrap()).await;
+        assert_eq!(messages(&after), ["12"]);
+    }
+
+    #[tokio::test]
+    async fn test_statuses_pages() {
+        let state = state();
+        add_checker_with_statuses(&state, "a", 10, 5).await;
+        let get = |limit, offset| {
+            get_checker_statuses(
+                State(state.clone()),
+                Path("a".to_string()),
+                Query(StatusesQuery {
+                    limit,
+                    offset,
+                    ..statuses_query(None).0
+                }),
+            )
+        };
+        let total_count = |response: &Response| {
+            response.headers()[TOTAL_COUNT_HEADER]
+                .to_str()
+                .unwrap()
+                .to_string()
+        };
+
+        let response = get(Some(2), Some(1)).await.unwrap();
+        assert_eq!(total_count(&response), "5");
+        let page: StatusRange = body(response).await;
+        assert_eq!(messages(&page), ["2", "3"]);
+        assert_eq!(page.total, 5);
+
+        let response = get(None, Some(5)).await.unwrap();
+        assert_eq!(total_count(&response), "5");
+        let page: StatusRange = body(response).await;
+        assert!(page.statuses.is_empty());
+        assert_eq!(page.total, 5);
+
+        let response = get(Some(0), None).await.unwrap();
+        assert_eq!(total_count(&response), "5");
+        let page: StatusRange = body(response).await;
+        assert!(page.statuses.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_status_at_index() {
+        let state = state();
+        add_checker_with_statuses(&state, "a", 10, 3).await;
+        let get = |index: &str| {
+            get_checker_status(
+                State(state.clone()),
+                Path(("a".to_string(), index.to_string())),
+            )
+        };
+        let error = |e: ApiError| async {
+            let response = e.into_response();
+            let status = response.status();
+            let swec_core::ErrorResponse { error } = body(response).await;
+            (status, error.code)
+        };
+
+        let Json((_, status)) = get("0").await.unwrap();
+        assert_eq!(status.message, "2");
+        let Json((_, status)) = get("2").await.unwrap();
+        assert_eq!(status.message, "0");
+        for index in ["-1", "first", "1.5"] {
+            let (status, code) = error(get(index).await.unwrap_err()).await;
+            assert_eq!(
+                (status, code.as_str()),
+                (StatusCode::BAD_REQUEST, "invalid_index")
+            );
+        }
+        let (status, code) = error(get("3").await.unwrap_err()).await;
+        assert_eq!(
+            (status, code.as_str()),
+            (StatusCode::NOT_FOUND, "status_not_found")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_replayed_statuses_deduplicated() {
+        let config = Config {
+            history_backend: HistoryBackend::BTreeMap,
+            out_of_order_statuses: OutOfOrderPolicy::Insert,
+            ..Config::default()
+        };
+        let state = state_with(true, config);
+        let name = || Path("team-a/web".to_string());
+        state
+            .2
+            .write()
+            .await
+            .add_checker("team-a/web".to_string(), spec())
+            .unwrap();
+        let t0 = Local::now();
+        let statuses = vec![
+            (t0, checker::Status::up("0")),
+            (
+                t0 + chrono::Duration::try_seconds(1).unwrap(),
+                checker::Status::up("1"),
+            ),
+        ];
+        // Replaying the batch replaces the statuses instead of adding them again.
+        for _ in 0..2 {
+            post_checker_statuses(
+                State(state.clone()),
+                name(),
+                team_a(),
+                Json(statuses.clone()),
+            )
+            .await
+            .unwrap();
+        }
+
+        let response = get_checker_statuses(State(state.clone()), name(), statuses_query(None))
+            .await
+            .unwrap();
+        let range: StatusRange = body(response).await;
+        assert_eq!(messages(&range), vec!["0", "1"]);
+        assert_eq!(range.head, 2);
+    }
+}
@@ -0,0 +1,166 @@
+use super::ApiError;
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts, Request, State},
+    http::{header, request::Parts, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::fmt::{self, Display, Formatter};
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// Who made a request, as far as the server can tell, e.g. to say who changed what in the audit
+/// log.
+#[derive(Debug, Clone)]
+pub struct Requester {
+    /// The address the request came from. This is that of the proxy for proxied requests.
+    pub address: Option<SocketAddr>,
+    pub identity: Identity,
+}
+
+/// How a request was authenticated.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Identity {
+    /// Without a token, because the route or the server doesn't require one
+    #[default]
+    Anonymous,
+    /// With the `api_token` of the configuration
+    ApiToken,
+    /// With one of the `prefixed_tokens` of the configuration, limited to checkers whose name
+    /// starts with the prefix
+    Prefixed(String),
+}
+
+impl Display for Identity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Anonymous => write!(f, "anonymous"),
+            Self::ApiToken => write!(f, "api_token"),
+            Self::Prefixed(prefix) => write!(f, "prefixed_token({prefix})"),
+        }
+    }
+}
+
+/// Formatted as `<identity>@<address>`, e.g. `api_token@192.0.2.1:51234`.
+impl Display for Requester {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.address {
+            Some(address) => write!(f, "{}@{address}", self.identity),
+            None => write!(f, "{}@unknown", self.identity),
+        }
+    }
+}
+
+/// A requester tried to change a checker outside of the prefix allowed for its token.
+#[derive(Debug)]
+pub struct ForbiddenName {
+    pub name: String,
+    pub prefix: String,
+}
+
+impl Requester {
+    /// Check whether the requester may change the checker with that name, or its history.
+    /// # Errors
+    /// Returns a `ForbiddenName` if the requester is limited to a prefix the name doesn't start
+    /// with.
+    pub fn check_name(&self, name: &str) -> Result<(), ForbiddenName> {
+        match &self.identity {
+            Identity::Prefixed(prefix) if !name.starts_with(prefix.as_str()) => {
+                Err(ForbiddenName {
+                    name: name.to_string(),
+                    prefix: prefix.clone(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Requester {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self {
+            address: parts
+                .extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(address)| *address),
+            identity: parts
+                .extensions
+                .get::<Identity>()
+                .cloned()
+                .unwrap_or_default(),
+        })
+    }
+}
+
+/// The tokens accepted by the read-write API.
+#[derive(Debug, Default)]
+pub struct Tokens {
+    pub api_token: Option<String>,
+    /// Tokens mapped to the prefix they are limited to
+    pub prefixed: BTreeMap<String, String>,
+}
+
+impl Tokens {
+    pub fn is_empty(&self) -> bool {
+        self.api_token.is_none() && self.prefixed.is_empty()
+    }
+
+    /// Find the identity that a token gives, if it is one of them.
+    fn identify(&self, given: &str) -> Option<Identity> {
+        // Every token is compared, so that the time taken doesn't tell which one was close.
+        let full = self
+            .api_token
+            .iter()
+            .filter(|token| constant_time_eq(given.as_bytes(), token.as_bytes()))
+            .map(|_| Identity::ApiToken);
+        let prefixed = self
+            .prefixed
+            .iter()
+            .filter(|(token, _)| constant_time_eq(given.as_bytes(), token.as_bytes()))
+            .map(|(_, prefix)| Identity::Prefixed(prefix.clone()));
+        full.chain(prefixed)
+            .fold(None, |found, identity| found.or(Some(identity)))
+    }
+}
+
+/// Middleware rejecting requests that don't have one of the tokens in an `Authorization: Bearer`
+/// header. Those that have one are marked with the `Identity` it gives.
+pub async fn require_token(
+    State(tokens): State<Arc<Tokens>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let identity = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .and_then(|given| tokens.identify(given));
+    match identity {
+        Some(identity) => {
+            request.extensions_mut().insert(identity);
+            next.run(request).await
+        }
+        None => (
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+            ApiError::new(
+                StatusCode::UNAUTHORIZED,
+                "unauthorized",
+                "This endpoint requires a valid bearer token",
+            ),
+        )
+            .into_response(),
+    }
+}
+
+/// Compare without stopping at the first difference, so that the time taken doesn't tell how
+/// much of a guessed token is right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -0,0 +1,57 @@
+use chrono::{DateTime, Local, SecondsFormat};
+use swec_core::checker;
+
+/// The first line of CSV exports.
+pub const HEADER: &str = "time,is_up,message\n";
+
+/// Format a status as a line of CSV, with the columns of `HEADER`.
+pub fn row(time: &DateTime<Local>, status: &checker::Status) -> String {
+    format!(
+        "{},{},{}\n",
+        time.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+        status.is_up(),
+        quote(&status.message)
+    )
+}
+
+/// Quote a field if it needs to be, doubling the quotes in it, as in RFC 4180.
+fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header() {
+        assert_eq!(HEADER, "time,is_up,message\n");
+    }
+
+    #[test]
+    fn test_row() {
+        let time = Local::now();
+        let formatted = time.to_rfc3339_opts(SecondsFormat::AutoSi, true);
+        let row = |status: checker::Status| row(&time, &status);
+        assert_eq!(
+            row(checker::Status::up("All good")),
+            format!("{formatted},true,All good\n")
+        );
+        assert_eq!(
+            row(checker::Status::down("Timeout, retrying")),
+            format!("{formatted},false,\"Timeout, retrying\"\n")
+        );
+        assert_eq!(
+            row(checker::Status::down("Got \"502\"")),
+            format!("{formatted},false,\"Got \"\"502\"\"\"\n")
+        );
+        assert_eq!(
+            row(checker::Status::down("Line 1\nLine 2")),
+            format!("{formatted},false,\"Line 1\nLine 2\"\n")
+        );
+    }
+}
@@ -0,0 +1,134 @@
+use super::{auth::ForbiddenName, CheckerAlreadyExists, CheckerDoesNotExist};
+use crate::history::OutOfOrderStatus;
+use axum::{
+    extract::{
+        rejection::{JsonRejection, PathRejection, QueryRejection},
+        FromRequest, FromRequestParts,
+    },
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use swec_core::{checker, ErrorDetail, ErrorResponse};
+
+/// An error returned by a handler.
+/// It is sent to the client as an `ErrorResponse`, so that all failures of the API have the same
+/// shape, including those of the extractors below.
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    code: &'static str,
+    message: String,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = ErrorResponse {
+            error: ErrorDetail {
+                code: self.code.to_string(),
+                message: self.message,
+            },
+        };
+        (self.status, axum::Json(body)).into_response()
+    }
+}
+
+impl From<CheckerDoesNotExist> for ApiError {
+    fn from(CheckerDoesNotExist(name): CheckerDoesNotExist) -> Self {
+        Self::new(
+            StatusCode::NOT_FOUND,
+            "checker_not_found",
+            format!("No checker named {name}"),
+        )
+    }
+}
+
+impl From<CheckerAlreadyExists> for ApiError {
+    fn from(CheckerAlreadyExists(name): CheckerAlreadyExists) -> Self {
+        Self::new(
+            StatusCode::CONFLICT,
+            "checker_already_exists",
+            format!("A checker named {name} already exists"),
+        )
+    }
+}
+
+impl From<ForbiddenName> for ApiError {
+    fn from(ForbiddenName { name, prefix }: ForbiddenName) -> Self {
+        Self::new(
+            StatusCode::FORBIDDEN,
+            "forbidden_name",
+            format!("The token only allows checkers whose name starts with {prefix}, not {name}"),
+        )
+    }
+}
+
+impl From<checker::InvalidSpec> for ApiError {
+    fn from(checker::InvalidSpec(reason): checker::InvalidSpec) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, "invalid_spec", reason)
+    }
+}
+
+impl From<OutOfOrderStatus> for ApiError {
+    fn from(OutOfOrderStatus { time, newest }: OutOfOrderStatus) -> Self {
+        Self::new(
+            StatusCode::CONFLICT,
+            "status_out_of_order",
+            format!("Status at {time} is older than the newest status, at {newest}"),
+        )
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        let code = if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            "body_too_large"
+        } else {
+            "invalid_body"
+        };
+        Self::new(rejection.status(), code, rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        Self::new(rejection.status(), "invalid_path", rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        Self::new(rejection.status(), "invalid_query", rejection.body_text())
+    }
+}
+
+/// `axum::Json`, with rejections sent as an `ApiError`.
+#[derive(Debug, FromRequest)]
+#[from_request(via(axum::Json), rejection(ApiError))]
+pub struct Json<T>(pub T);
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        axum::Json(self.0).into_response()
+    }
+}
+
+/// `axum::extract::Path`, with rejections sent as an `ApiError`.
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(ApiError))]
+pub struct Path<T>(pub T);
+
+/// `axum::extract::Query`, with rejections sent as an `ApiError`.
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(ApiError))]
+pub struct Query<T>(pub T);
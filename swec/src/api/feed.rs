@@ -0,0 +1,72 @@
+use crate::StatusHistory;
+use chrono::{DateTime, Local, SecondsFormat};
+use swec_core::checker;
+
+/// Maximum number of entries in a feed, newest first.
+const FEED_LEN: usize = 50;
+
+/// Build an Atom feed of the transitions of a checker, with an entry each time it went up or
+/// down.
+pub fn atom_feed(name: &str, spec: &checker::Spec, statuses: &dyn StatusHistory) -> String {
+    let title = escape(&spec.description);
+    let entries: Vec<_> = statuses
+        .transitions()
+        .into_iter()
+        .rev()
+        .take(FEED_LEN)
+        .collect();
+    // Atom requires the feed to have an update time, even without any entry.
+    let updated = entries
+        .first()
+        .map(|(time, _)| *time)
+        .or_else(|| statuses.newest_time())
+        .unwrap_or_else(Local::now);
+
+    let mut feed = String::new();
+    feed.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    feed.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    feed.push_str(&format!("  <id>{}</id>\n", id(name, None)));
+    feed.push_str(&format!("  <title>{title}</title>\n"));
+    feed.push_str(&format!("  <updated>{}</updated>\n", format_time(updated)));
+    feed.push_str("  <author><name>swec</name></author>\n");
+    for (time, status) in entries {
+        let state = if status.is_up() { "up" } else { "down" };
+        feed.push_str("  <entry>\n");
+        feed.push_str(&format!("    <id>{}</id>\n", id(name, Some(time))));
+        let time = format_time(time);
+        feed.push_str(&format!(
+            "    <title>{title} went {state} at {time}</title>\n"
+        ));
+        feed.push_str(&format!("    <updated>{time}</updated>\n"));
+        feed.push_str(&format!(
+            "    <summary>{}</summary>\n",
+            escape(&status.message)
+        ));
+        feed.push_str("  </entry>\n");
+    }
+    feed.push_str("</feed>\n");
+    feed
+}
+
+/// A permanent identifier for the feed of a checker, or for one of its entries.
+fn id(name: &str, time: Option<DateTime<Local>>) -> String {
+    let mut id = format!("urn:swec:checker:{}", escape(name));
+    if let Some(time) = time {
+        // With full precision, so that transitions within a second get different identifiers.
+        id.push(':');
+        id.push_str(&time.to_rfc3339_opts(SecondsFormat::AutoSi, true));
+    }
+    id
+}
+
+fn format_time(time: DateTime<Local>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
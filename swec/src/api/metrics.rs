@@ -0,0 +1,52 @@
+use crate::History;
+use swec_core::checker;
+
+/// Render gauges of every checker in the Prometheus text exposition format.
+/// Only the newest status of each checker is looked at, so this is cheap even with long
+/// histories.
+pub fn prometheus<'a>(
+    checkers: impl Iterator<Item = (&'a String, &'a checker::Checker<History>)>,
+) -> String {
+    let checkers: Vec<_> = checkers
+        .map(|(name, checker)| {
+            let latest = checker.statuses.iter().next_back();
+            (escape_label_value(name), checker.statuses.len(), latest)
+        })
+        .collect();
+    let mut out = String::new();
+    out.push_str("# HELP swec_checker_up Whether the latest status of the checker is up.\n");
+    out.push_str("# TYPE swec_checker_up gauge\n");
+    for (name, _, latest) in &checkers {
+        if let Some((_, status)) = latest {
+            let up = u8::from(status.is_up());
+            out.push_str(&format!("swec_checker_up{{name=\"{name}\"}} {up}\n"));
+        }
+    }
+    out.push_str("# HELP swec_checker_last_status_timestamp_seconds Time of the latest status of the checker.\n");
+    out.push_str("# TYPE swec_checker_last_status_timestamp_seconds gauge\n");
+    for (name, _, latest) in &checkers {
+        if let Some((time, _)) = latest {
+            out.push_str(&format!(
+                "swec_checker_last_status_timestamp_seconds{{name=\"{name}\"}} {}\n",
+                time.timestamp()
+            ));
+        }
+    }
+    out.push_str(
+        "# HELP swec_checker_status_count Number of statuses in the history of the checker.\n",
+    );
+    out.push_str("# TYPE swec_checker_status_count gauge\n");
+    for (name, count, _) in &checkers {
+        out.push_str(&format!(
+            "swec_checker_status_count{{name=\"{name}\"}} {count}\n"
+        ));
+    }
+    out
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,78 @@
+use crate::config::PublicSpecFields;
+use std::collections::BTreeMap;
+use swec_core::{checker, CheckerMessage, GlobalMessage, ListMessage, TransitionMessage};
+
+/// Values sent by the API that contain specs, whose fields the read-only API may not expose.
+pub trait ContainsSpecs {
+    /// Strip the fields that are not in `fields` from every spec in the value.
+    fn strip_specs(&mut self, fields: PublicSpecFields);
+}
+
+impl ContainsSpecs for checker::Spec {
+    fn strip_specs(&mut self, fields: PublicSpecFields) {
+        if !fields.url {
+            self.url = None;
+        }
+        if !fields.group {
+            self.group = None;
+        }
+    }
+}
+
+impl<B: checker::StatusBuffer> ContainsSpecs for checker::Checker<B> {
+    fn strip_specs(&mut self, fields: PublicSpecFields) {
+        self.spec.strip_specs(fields);
+    }
+}
+
+impl<T: ContainsSpecs> ContainsSpecs for Option<T> {
+    fn strip_specs(&mut self, fields: PublicSpecFields) {
+        if let Some(value) = self {
+            value.strip_specs(fields);
+        }
+    }
+}
+
+impl<T: ContainsSpecs> ContainsSpecs for BTreeMap<String, T> {
+    fn strip_specs(&mut self, fields: PublicSpecFields) {
+        for value in self.values_mut() {
+            value.strip_specs(fields);
+        }
+    }
+}
+
+impl ContainsSpecs for CheckerMessage {
+    fn strip_specs(&mut self, fields: PublicSpecFields) {
+        match self {
+            Self::Initial { spec, .. } | Self::UpdatedSpec { spec } => spec.strip_specs(fields),
+            Self::AddedStatus { .. }
+            | Self::Transition { .. }
+            | Self::MaintenanceChanged { .. }
+            | Self::HistoryCleared
+            | Self::CheckerDropped
+            | Self::Lagged { .. } => {}
+        }
+    }
+}
+
+impl ContainsSpecs for ListMessage {
+    fn strip_specs(&mut self, _: PublicSpecFields) {}
+}
+
+impl ContainsSpecs for TransitionMessage {
+    fn strip_specs(&mut self, _: PublicSpecFields) {}
+}
+
+impl ContainsSpecs for GlobalMessage {
+    fn strip_specs(&mut self, fields: PublicSpecFields) {
+        match self {
+            Self::Initial { checkers } => {
+                for (spec, _) in checkers.values_mut() {
+                    spec.strip_specs(fields);
+                }
+            }
+            Self::Checker { message, .. } => message.strip_specs(fields),
+            Self::List { .. } | Self::Lagged { .. } => {}
+        }
+    }
+}
@@ -0,0 +1,256 @@
+use crate::{
+    groups::RollupPolicy,
+    history::{HistoryBackend, OutOfOrderPolicy},
+    persistence,
+};
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+/// Which optional fields of specs the read-only API exposes. The read-write API always exposes
+/// all of them.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PublicSpecFields {
+    /// Whether to expose the URL of services, which may be internal
+    pub url: bool,
+    /// Whether to expose the group of checkers
+    pub group: bool,
+}
+
+impl Default for PublicSpecFields {
+    fn default() -> Self {
+        Self {
+            url: true,
+            group: true,
+        }
+    }
+}
+
+/// How logs are written, both to the standard output and to the audit log.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum LogFormat {
+    /// One human-readable line per event
+    #[default]
+    Text,
+    /// One JSON object per event, with the fields of the event and of its spans, for log
+    /// pipelines
+    Json,
+}
+
+/// The effective configuration of the server.
+///
+/// This is exposed as-is by the `/admin/config` endpoint, so any secret added here must be
+/// redacted when serialized.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Backend the checkers are dumped to and restored from
+    pub persistence: persistence::Backend,
+    /// File the checkers are dumped to and restored from, for file backends, or `<bucket>/<key>`
+    /// of the object they are dumped to for the S3 backend
+    pub dump_path: PathBuf,
+    /// Endpoint of the S3 backend, for S3-compatible stores. `None` for that of AWS in
+    /// `s3_region`.
+    pub s3_endpoint: Option<String>,
+    /// Region of the bucket of the S3 backend
+    pub s3_region: String,
+    /// Access key of the S3 backend. Without it and `s3_secret_access_key`, they are read from
+    /// the `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY` environment variables, and requests
+    /// are anonymous if those aren't set either.
+    pub s3_access_key_id: Option<String>,
+    /// Secret of `s3_access_key_id`
+    #[serde(serialize_with = "redact")]
+    pub s3_secret_access_key: Option<String>,
+    /// Whether to indent JSON dumps so that they are easier to read, at the cost of size
+    pub pretty_dump: bool,
+    /// Time between two dumps, in seconds
+    pub dump_interval: u64,
+    /// Maximum random delay added to each dump interval, in seconds, so that instances sharing a
+    /// disk don't all dump at the same time
+    pub dump_splay: u64,
+    /// Number of dump intervals dumps can fail for before the server reports itself as not ready
+    pub failing_dumps_tolerance: u32,
+    /// How the histories of checkers are kept in memory. `btree-map` merges statuses observed at
+    /// the same time, e.g. when replaying statuses that were already posted, instead of keeping
+    /// both.
+    pub history_backend: HistoryBackend,
+    /// Number of statuses kept for each checker, unless its spec sets its own
+    pub history_len: usize,
+    /// Age in seconds beyond which statuses are dropped, e.g. 86400 to keep the last day, even if
+    /// there are fewer than `history_len`. They are dropped when the checker gets a new status.
+    pub history_max_age: Option<u64>,
+    /// Whether to drop the oldest statuses of restored histories longer than `history_len`,
+    /// instead of keeping them
+    pub truncate_histories: bool,
+    /// Whether to add a status to each restored checker saying that swec restarted, so that
+    /// dashboards don't show the dumped status as if it were current until new data comes in
+    pub restart_status: bool,
+    /// Whether to reject specs that fail validation, e.g. with a malformed URL. Disable this to
+    /// store values other than URLs in the URL field.
+    pub validate_specs: bool,
+    /// What to do with posted statuses older than the newest status of their checker
+    pub out_of_order_statuses: OutOfOrderPolicy,
+    /// Longest time between two statuses, in seconds, over which the checker is still assumed to
+    /// be in the state of the first one when computing uptime. Longer gaps count as unknown.
+    /// `None` to never assume gaps are unknown.
+    pub max_status_gap: Option<u64>,
+    /// Address of the read-only API
+    pub public_address: String,
+    /// Address of the read-write API
+    pub private_address: String,
+    /// Time given to websocket clients to close their connections on shutdown, in seconds
+    pub shutdown_grace_period: u64,
+    /// Number of consecutive statuses a checker must have in a new state before the transition is
+    /// reported
+    pub transition_damping_statuses: Option<usize>,
+    /// Time a checker must spend in a new state before the transition is reported, in seconds.
+    /// With neither this nor `transition_damping_statuses`, transitions are reported immediately.
+    pub transition_damping_secs: Option<u64>,
+    /// How many checkers of a group must be up for the group to be healthy
+    pub group_rollup: RollupPolicy,
+    /// Maximum number of pending connections on each API listener, beyond which new connections
+    /// are refused
+    pub listen_backlog: u32,
+    /// Idle time after which TCP keepalive probes are sent on API connections, in seconds, or
+    /// `None` to disable keepalive
+    pub tcp_keepalive: Option<u64>,
+    /// File to which changes to the checker set are appended, in addition to the regular log
+    pub audit_log: Option<PathBuf>,
+    /// Format of the logs
+    pub log_format: LogFormat,
+    /// Whether to log each request to the API, with its method, URI, response status and
+    /// latency
+    pub access_log: bool,
+    /// Whether to include the headers of requests in the access log. They include the API token
+    /// of authenticated requests, so only enable this while debugging.
+    pub access_log_headers: bool,
+    /// Fields of specs exposed by the read-only API, e.g. to serve a public status page without
+    /// internal URLs
+    pub public_spec_fields: PublicSpecFields,
+    /// Path under which the API is served
+    pub api_path: String,
+    /// Token that requests to the read-write API must have as a bearer token, except for the
+    /// routes that are also in the read-only API. `None` to leave it open to anyone who can
+    /// reach it.
+    #[serde(serialize_with = "redact")]
+    pub api_token: Option<String>,
+    /// Tokens accepted like `api_token`, each mapped to the prefix that the names of the checkers
+    /// it changes, or posts statuses to, must start with, e.g. to give each team of a shared
+    /// server its own namespace. When set, the read-write API requires a token even without
+    /// `api_token`.
+    #[serde(serialize_with = "redact_tokens")]
+    pub prefixed_tokens: BTreeMap<String, String>,
+    /// Largest body accepted by the write routes of the read-write API, in bytes, beyond which
+    /// requests are rejected with 413
+    pub max_body_size: usize,
+    /// Largest body accepted when posting a batch of statuses, in bytes, which is larger than
+    /// `max_body_size` since batches hold many statuses
+    pub max_batch_body_size: usize,
+    /// Longest message accepted in posted statuses, in bytes
+    pub max_message_len: usize,
+    /// Time between two pings sent to websocket clients, in seconds, which also keeps their
+    /// connections alive through proxies that close idle ones. `None` to never ping them.
+    pub ws_ping_interval: Option<u64>,
+    /// Number of messages queued for those watching a checker, unless its spec sets its own, or
+    /// watching the list of checkers, before the slowest of them lag and miss messages. Raise it
+    /// for checkers that get statuses faster than watchers read them. Each queued message is kept
+    /// in memory until every watcher has read it or it is overwritten, so this bounds the memory
+    /// used per checker, and larger values let a slow watcher hold on to more of it.
+    /// Watchers of all checkers at once use `global_channel_capacity` instead.
+    pub channel_capacity: usize,
+    /// Number of messages queued for those watching all checkers at once, through `/watch_all`
+    /// and `/transitions`, before the slowest of them lag and miss messages. These get the
+    /// messages of every checker, so this should grow with the number of checkers and the rate
+    /// of their statuses, with the same memory tradeoff as `channel_capacity`.
+    pub global_channel_capacity: usize,
+    /// Time websocket clients have to answer a ping, in seconds, before they are disconnected
+    pub ws_pong_timeout: u64,
+}
+
+fn redact<S: Serializer>(secret: &Option<String>, serializer: S) -> Result<S::Ok, S::Error> {
+    secret.as_ref().map(|_| "<redacted>").serialize(serializer)
+}
+
+/// Only the prefixes are serialized, since the tokens are secrets.
+fn redact_tokens<S: Serializer>(
+    tokens: &BTreeMap<String, String>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(tokens.values())
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            persistence: persistence::Backend::default(),
+            dump_path: PathBuf::from("swec_dump.json"),
+            s3_endpoint: None,
+            s3_region: "us-east-1".to_string(),
+            s3_access_key_id: None,
+            s3_secret_access_key: None,
+            pretty_dump: false,
+            dump_interval: 60,
+            dump_splay: 5,
+            failing_dumps_tolerance: 3,
+            history_backend: HistoryBackend::default(),
+            history_len: 3600,
+            history_max_age: None,
+            truncate_histories: false,
+            restart_status: false,
+            validate_specs: true,
+            out_of_order_statuses: OutOfOrderPolicy::default(),
+            max_status_gap: Some(300),
+            public_address: "127.0.0.1:8080".to_string(),
+            private_address: "127.0.0.1:8081".to_string(),
+            shutdown_grace_period: 2,
+            transition_damping_statuses: None,
+            transition_damping_secs: None,
+            group_rollup: RollupPolicy::default(),
+            listen_backlog: 1024,
+            tcp_keepalive: Some(60),
+            audit_log: None,
+            log_format: LogFormat::default(),
+            access_log: true,
+            access_log_headers: false,
+            public_spec_fields: PublicSpecFields::default(),
+            api_path: "/api/v1".to_string(),
+            api_token: None,
+            prefixed_tokens: BTreeMap::new(),
+            max_body_size: 16 * 1024,
+            max_batch_body_size: 1024 * 1024,
+            max_message_len: 1024,
+            channel_capacity: 16,
+            global_channel_capacity: 64,
+            ws_ping_interval: Some(30),
+            ws_pong_timeout: 10,
+        }
+    }
+}
+
+impl Config {
+    /// Read the configuration from a TOML file, with the default value of every field it leaves
+    /// out, or use the defaults if there is no file. Either way, the addresses can be overridden
+    /// by the `SWEC_PUBLIC_ADDRESS` and `SWEC_PRIVATE_ADDRESS` environment variables, which is
+    /// handier in containers.
+    pub fn load(path: Option<&Path>) -> Result<Self, Box<dyn Error>> {
+        let mut config = match path {
+            Some(path) => {
+                let contents = std::fs::read_to_string(path)
+                    .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+                toml::from_str(&contents)
+                    .map_err(|e| format!("Invalid configuration in {}: {e}", path.display()))?
+            }
+            None => Self::default(),
+        };
+        if let Ok(address) = std::env::var("SWEC_PUBLIC_ADDRESS") {
+            config.public_address = address;
+        }
+        if let Ok(address) = std::env::var("SWEC_PRIVATE_ADDRESS") {
+            config.private_address = address;
+        }
+        Ok(config)
+    }
+}
@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use swec_core::{checker, GroupState, GroupStatus};
+
+/// How many checkers of a group must be up for the group to be healthy.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RollupPolicy {
+    /// All of them
+    #[default]
+    AllUp,
+    /// More than half of them
+    Majority,
+    /// At least one of them
+    AnyUp,
+}
+
+impl RollupPolicy {
+    fn state(self, up: usize, down: usize) -> GroupState {
+        let total = up + down;
+        if total == 0 {
+            return GroupState::Unknown;
+        }
+        if up == 0 {
+            return GroupState::Down;
+        }
+        let healthy = match self {
+            Self::AllUp => down == 0,
+            Self::Majority => up * 2 > total,
+            Self::AnyUp => true,
+        };
+        if healthy {
+            GroupState::Healthy
+        } else {
+            GroupState::Degraded
+        }
+    }
+}
+
+/// Compute the status of every group from the specs and latest statuses of the checkers.
+/// Checkers without a group are left out.
+pub fn roll_up<'a>(
+    checkers: impl IntoIterator<Item = (&'a String, &'a checker::Spec, Option<&'a checker::Status>)>,
+    policy: RollupPolicy,
+) -> BTreeMap<String, GroupStatus> {
+    let mut groups = BTreeMap::new();
+    for (name, spec, latest) in checkers {
+        let Some(group) = &spec.group else {
+            continue;
+        };
+        let status = groups.entry(group.clone()).or_insert_with(|| GroupStatus {
+            state: GroupState::Unknown,
+            up: Vec::new(),
+            down: Vec::new(),
+            unknown: Vec::new(),
+        });
+        match latest {
+            Some(latest) if spec.enabled && latest.is_up() => status.up.push(name.clone()),
+            Some(_) if spec.enabled => status.down.push(name.clone()),
+            _ => status.unknown.push(name.clone()),
+        }
+    }
+    for status in groups.values_mut() {
+        status.state = policy.state(status.up.len(), status.down.len());
+    }
+    groups
+}
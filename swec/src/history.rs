@@ -0,0 +1,621 @@
+use crate::StatusRingBuffer;
+use chrono::{DateTime, Local};
+use core::fmt::Debug;
+use serde::{ser::SerializeStruct, Deserialize, Serialize, Serializer};
+use std::ops::Bound;
+use swec_core::{BTreeMapBuffer, Health, LatestStatus, Status, StatusBuffer, Uptime};
+
+/// A status of a history, borrowed from it.
+pub type Entry<'a> = (DateTime<Local>, &'a Status);
+
+/// An iterator over statuses of a history, oldest first.
+pub type Iter<'a> = Box<dyn DoubleEndedIterator<Item = Entry<'a>> + 'a>;
+
+/// The history of a checker, in the backend selected in the configuration.
+pub type History = Box<dyn StatusHistory>;
+
+/// What to do with a status older than the newest status of a history.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutOfOrderPolicy {
+    /// Refuse the status
+    #[default]
+    Reject,
+    /// Insert the status at its place in the history
+    Insert,
+}
+
+/// A status was rejected because it is older than the newest status of the history.
+#[derive(Debug)]
+pub struct OutOfOrderStatus {
+    pub time: DateTime<Local>,
+    pub newest: DateTime<Local>,
+}
+
+/// The ways histories can be kept in memory, selected in the configuration.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HistoryBackend {
+    /// A `StatusRingBuffer`, which keeps every status, even those observed at the same time
+    #[default]
+    RingBuffer,
+    /// A `BTreeMapHistory`, in which a status replaces any other observed at the same time, e.g.
+    /// when replaying statuses that were already posted
+    #[serde(rename = "btree-map")]
+    BTreeMap,
+}
+
+impl HistoryBackend {
+    /// Create an empty history keeping up to `capacity` statuses.
+    #[must_use]
+    pub fn new_history(self, capacity: usize) -> History {
+        match self {
+            Self::RingBuffer => Box::new(StatusRingBuffer::new(capacity)),
+            Self::BTreeMap => Box::new(BTreeMapHistory::new(capacity)),
+        }
+    }
+
+    /// Move a history to this backend, keeping its capacity and head, e.g. after restoring it
+    /// from a dump. Statuses observed at the same time are merged when moving to `BTreeMap`.
+    #[must_use]
+    pub fn convert(self, history: History) -> History {
+        if history.backend() == self {
+            return history;
+        }
+        let mut converted = self.new_history(history.capacity());
+        for (time, status) in history.iter() {
+            converted.push((time, status.clone()));
+        }
+        converted.set_head(history.head_index());
+        converted
+    }
+}
+
+/// A history of statuses, always sorted by time, oldest first, with what the API and the
+/// persistence backends need on top of `StatusBuffer`.
+/// Backends only implement how statuses are stored: everything computed from them, like uptime
+/// or downsampling, is provided.
+pub trait StatusHistory: StatusBuffer + Debug + Send + Sync {
+    /// The backend this history is kept in.
+    fn backend(&self) -> HistoryBackend;
+
+    /// Clone the history into a new box, to clone checkers.
+    fn clone_box(&self) -> History;
+
+    /// Get the maximum number of statuses kept, beyond which the oldest ones are dropped.
+    fn capacity(&self) -> usize;
+
+    /// Change the capacity, dropping the oldest statuses as necessary.
+    fn truncate_fifo(&mut self, capacity: usize);
+
+    /// Set the index the next status added will have, e.g. to restore the head of a history
+    /// from a dump, see `RingBuffer::set_head`. It can't be less than the length of the history.
+    fn set_head(&mut self, head: u64);
+
+    /// Remove all statuses, keeping the capacity and the head.
+    fn clear(&mut self);
+
+    /// Get an iterator over the statuses.
+    fn iter(&self) -> Iter<'_>;
+
+    /// Get an iterator over the statuses observed within the given bounds.
+    fn statuses_in(&self, bounds: (Bound<DateTime<Local>>, Bound<DateTime<Local>>)) -> Iter<'_>;
+
+    /// Get an iterator over the statuses whose index is at least `index`, or over all statuses
+    /// if `index` is past the head, see `RingBuffer::iter_since`.
+    fn iter_since(&self, index: u64) -> Iter<'_>;
+
+    /// Add a status, keeping the history sorted according to the policy.
+    /// # Errors
+    /// Returns an `OutOfOrderStatus` if the status is older than the newest one and the policy is
+    /// to reject such statuses, or if the history is full and the status is older than all of it,
+    /// since it would be dropped right away.
+    fn push_status(
+        &mut self,
+        status: (DateTime<Local>, Status),
+        policy: OutOfOrderPolicy,
+    ) -> Result<(), OutOfOrderStatus>;
+
+    /// Get the time of the newest status.
+    fn newest_time(&self) -> Option<DateTime<Local>> {
+        self.iter().next_back().map(|(time, _)| time)
+    }
+
+    /// Get an iterator over the statuses observed since `since` (inclusive) and until `until`
+    /// (exclusive). A missing bound means no limit on that side.
+    fn range(&self, since: Option<DateTime<Local>>, until: Option<DateTime<Local>>) -> Iter<'_> {
+        self.statuses_in((
+            since.map_or(Bound::Unbounded, Bound::Included),
+            until.map_or(Bound::Unbounded, Bound::Excluded),
+        ))
+    }
+
+    /// Get the status that was current at the given time, i.e. the newest one observed at or
+    /// before it.
+    fn status_at(&self, time: DateTime<Local>) -> Option<Entry<'_>> {
+        self.statuses_in((Bound::Unbounded, Bound::Included(time)))
+            .next_back()
+    }
+
+    /// Get the latest status, along with the time of the oldest status of the run of statuses
+    /// in the same state (up or down) leading up to it, i.e. when the current state began.
+    /// If the whole history is in the same state, this is the time of the oldest status kept.
+    fn latest_with_state_since(&self) -> Option<LatestStatus> {
+        let (time, status) = self.iter().next_back()?;
+        let since = self
+            .iter()
+            .rev()
+            .take_while(|(_, s)| s.is_up() == status.is_up())
+            .last()
+            .map_or(time, |(since, _)| since);
+        Some(LatestStatus {
+            time,
+            status: status.clone(),
+            since,
+        })
+    }
+
+    /// Compute how long the checker was up and down from `since` (or the oldest status) until
+    /// `until` (or `now`).
+    /// Each status is taken to last until the next one, or until `now` for the newest, unless
+    /// that is longer than `max_gap`: the time until the next status is then unknown rather than
+    /// in the state of the status, so that outages of the checker don't count as uptime.
+    fn uptime(
+        &self,
+        since: Option<DateTime<Local>>,
+        until: Option<DateTime<Local>>,
+        now: DateTime<Local>,
+        max_gap: Option<chrono::Duration>,
+    ) -> Uptime {
+        let end = until.map_or(now, |until| until.min(now));
+        let start = since
+            .or_else(|| self.iter().next().map(|(time, _)| time))
+            .unwrap_or(end);
+        let mut up = chrono::Duration::zero();
+        let mut down = chrono::Duration::zero();
+        let mut unknown = chrono::Duration::zero();
+        // Everything before `accounted` is in one of the totals.
+        let mut accounted = start;
+
+        // Start from the status that was current at the start, if any.
+        let mut statuses = self
+            .status_at(start)
+            .into_iter()
+            .chain(self.statuses_in((Bound::Excluded(start), Bound::Unbounded)))
+            .peekable();
+        while let Some((time, status)) = statuses.next() {
+            if time >= end {
+                break;
+            }
+            let next = statuses.peek().map_or(now, |(next, _)| *next);
+            let (from, to) = (time.max(start), next.min(end));
+            if from > accounted {
+                unknown += from - accounted;
+            }
+            if to > from {
+                let total = if max_gap.is_some_and(|max_gap| next - time > max_gap) {
+                    &mut unknown
+                } else if status.is_up() {
+                    &mut up
+                } else {
+                    &mut down
+                };
+                *total += to - from;
+            }
+            accounted = accounted.max(to);
+        }
+        if end > accounted {
+            unknown += end - accounted;
+        }
+
+        let secs = |d: chrono::Duration| d.num_milliseconds() as f64 / 1000.0;
+        let known = up + down;
+        let ratio = (known > chrono::Duration::zero()).then(|| secs(up) / secs(known));
+        Uptime {
+            up_secs: secs(up),
+            down_secs: secs(down),
+            unknown_secs: secs(unknown),
+            uptime: ratio,
+            percent: ratio.map(|ratio| ratio * 100.0),
+            samples: self.range(Some(start), Some(end)).count(),
+        }
+    }
+
+    /// Get the statuses at which the checker changed state (up or down), oldest first. The
+    /// oldest status kept is never one, since the state before it is unknown.
+    fn transitions(&self) -> Vec<Entry<'_>> {
+        self.iter()
+            .zip(self.iter().skip(1))
+            .filter(|((_, previous), (_, status))| previous.is_up() != status.is_up())
+            .map(|(_, status)| status)
+            .collect()
+    }
+
+    /// Drop the statuses observed more than `max_age` before the newest one, e.g. to keep the
+    /// last 24 hours however many statuses that is.
+    fn evict_older_than(&mut self, max_age: chrono::Duration) {
+        if let Some(cutoff) = self
+            .newest_time()
+            .and_then(|newest| newest.checked_sub_signed(max_age))
+        {
+            self.prune_before(cutoff);
+        }
+    }
+
+    /// Collapse the statuses into one per time bucket, e.g. to draw months of history without
+    /// sending every status. Buckets are aligned on the Unix epoch, and each is represented by
+    /// its first status with the worst health, timed at the start of the bucket. Buckets without
+    /// statuses are left out.
+    fn downsample(&self, bucket: chrono::Duration) -> Vec<(DateTime<Local>, Status)> {
+        let bucket_ms = bucket.num_milliseconds().max(1);
+        let severity = |status: &Status| match status.health {
+            Health::Up => 0,
+            Health::Degraded => 1,
+            Health::Down => 2,
+        };
+        let mut buckets: Vec<(i64, &Status)> = Vec::new();
+        for (time, status) in self.iter() {
+            let key = time.timestamp_millis().div_euclid(bucket_ms);
+            match buckets.last_mut() {
+                Some((last_key, worst)) if *last_key == key => {
+                    if severity(status) > severity(worst) {
+                        *worst = status;
+                    }
+                }
+                _ => buckets.push((key, status)),
+            }
+        }
+        buckets
+            .into_iter()
+            .filter_map(|(key, status)| {
+                let start = DateTime::from_timestamp_millis(key * bucket_ms)?;
+                Some((start.with_timezone(&Local), status.clone()))
+            })
+            .collect()
+    }
+
+    /// Check whether a status observed at the given time can be added as-is under the policy.
+    /// # Errors
+    /// Returns an `OutOfOrderStatus` if the policy is to reject statuses older than the newest
+    /// one and the time is older than the newest status.
+    fn check_order(
+        &self,
+        time: DateTime<Local>,
+        policy: OutOfOrderPolicy,
+    ) -> Result<(), OutOfOrderStatus> {
+        match (policy, self.newest_time()) {
+            (OutOfOrderPolicy::Reject, Some(newest)) if time < newest => {
+                Err(OutOfOrderStatus { time, newest })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Clone for History {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+impl StatusBuffer for History {
+    fn push(&mut self, status: (DateTime<Local>, Status)) {
+        (**self).push(status);
+    }
+
+    fn get(&self, index: usize) -> Option<(DateTime<Local>, Status)> {
+        (**self).get(index)
+    }
+
+    fn len(&self) -> usize {
+        (**self).len()
+    }
+
+    fn head_index(&self) -> u64 {
+        (**self).head_index()
+    }
+
+    fn prune_before(&mut self, time: DateTime<Local>) {
+        (**self).prune_before(time);
+    }
+
+    /// Histories built from a bare list of statuses are ring buffers, the default backend.
+    fn from_vec(vec: Vec<(DateTime<Local>, Status)>) -> Self {
+        Box::new(StatusRingBuffer::from_vec(vec))
+    }
+
+    fn as_vec(&self) -> Vec<(DateTime<Local>, Status)> {
+        (**self).as_vec()
+    }
+}
+
+/// Serialized like `RingBuffer`, as `{"capacity": n, "items": [...]}`, whatever the backend, so
+/// that dumps can be restored with another one.
+impl Serialize for dyn StatusHistory {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        struct Items<'a>(&'a dyn StatusHistory);
+
+        impl Serialize for Items<'_> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_seq(self.0.iter())
+            }
+        }
+
+        let mut state = serializer.serialize_struct("RingBuffer", 2)?;
+        state.serialize_field("capacity", &self.capacity())?;
+        state.serialize_field("items", &Items(self))?;
+        state.end()
+    }
+}
+
+/// A `BTreeMapBuffer` with the capacity and head of a `RingBuffer`.
+/// Statuses are keyed by time, so a status observed at the same time as one already in the
+/// history replaces it instead of being added next to it. Replacing a status doesn't move the
+/// head, since the status at that index was already there.
+#[derive(Debug, Clone)]
+pub struct BTreeMapHistory {
+    statuses: BTreeMapBuffer,
+    capacity: usize,
+    /// The number of statuses ever added, see `RingBuffer::head`
+    head: u64,
+}
+
+impl BTreeMapHistory {
+    #[must_use]
+    pub const fn new(capacity: usize) -> Self {
+        Self {
+            statuses: BTreeMapBuffer::new(),
+            capacity,
+            head: 0,
+        }
+    }
+
+    /// Insert a status, dropping the oldest one if that makes the history longer than its
+    /// capacity.
+    fn insert(&mut self, (time, status): (DateTime<Local>, Status)) {
+        if self.statuses.insert(time, status).is_none() {
+            self.head += 1;
+            if self.statuses.len() > self.capacity {
+                self.statuses.pop_first();
+            }
+        }
+    }
+}
+
+impl StatusBuffer for BTreeMapHistory {
+    fn push(&mut self, status: (DateTime<Local>, Status)) {
+        self.insert(status);
+    }
+
+    fn get(&self, index: usize) -> Option<(DateTime<Local>, Status)> {
+        StatusBuffer::get(&self.statuses, index)
+    }
+
+    fn len(&self) -> usize {
+        self.statuses.len()
+    }
+
+    fn head_index(&self) -> u64 {
+        self.head
+    }
+
+    fn prune_before(&mut self, time: DateTime<Local>) {
+        self.statuses.prune_before(time);
+    }
+
+    fn from_vec(vec: Vec<(DateTime<Local>, Status)>) -> Self {
+        let statuses = BTreeMapBuffer::from_vec(vec);
+        Self {
+            capacity: statuses.len(),
+            head: statuses.len() as u64,
+            statuses,
+        }
+    }
+
+    fn as_vec(&self) -> Vec<(DateTime<Local>, Status)> {
+        self.statuses.as_vec()
+    }
+}
+
+impl StatusHistory for BTreeMapHistory {
+    fn backend(&self) -> HistoryBackend {
+        HistoryBackend::BTreeMap
+    }
+
+    fn clone_box(&self) -> History {
+        Box::new(self.clone())
+    }
+
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    fn truncate_fifo(&mut self, capacity: usize) {
+        while self.statuses.len() > capacity {
+            self.statuses.pop_first();
+        }
+        self.capacity = capacity;
+    }
+
+    fn set_head(&mut self, head: u64) {
+        self.head = head.max(self.statuses.len() as u64);
+    }
+
+    fn clear(&mut self) {
+        self.statuses.clear();
+    }
+
+    fn iter(&self) -> Iter<'_> {
+        Box::new(self.statuses.iter().map(|(time, status)| (*time, status)))
+    }
+
+    fn statuses_in(&self, bounds: (Bound<DateTime<Local>>, Bound<DateTime<Local>>)) -> Iter<'_> {
+        // `BTreeMap::range` panics on bounds that don't delimit a range, which are empty.
+        if let (
+            Bound::Included(since) | Bound::Excluded(since),
+            Bound::Included(until) | Bound::Excluded(until),
+        ) = bounds
+        {
+            let both_excluded = matches!(bounds, (Bound::Excluded(_), Bound::Excluded(_)));
+            if since > until || (since == until && both_excluded) {
+                return Box::new(std::iter::empty());
+            }
+        }
+        Box::new(
+            self.statuses
+                .range(bounds)
+                .map(|(time, status)| (*time, status)),
+        )
+    }
+
+    fn iter_since(&self, index: u64) -> Iter<'_> {
+        let first = self.head - self.statuses.len() as u64;
+        let skip = if index > self.head {
+            0
+        } else {
+            usize::try_from(index.saturating_sub(first)).unwrap_or(usize::MAX)
+        };
+        Box::new(
+            self.statuses
+                .iter()
+                .skip(skip)
+                .map(|(time, status)| (*time, status)),
+        )
+    }
+
+    fn push_status(
+        &mut self,
+        status: (DateTime<Local>, Status),
+        policy: OutOfOrderPolicy,
+    ) -> Result<(), OutOfOrderStatus> {
+        let time = status.0;
+        self.check_order(time, policy)?;
+        let is_full = self.statuses.len() >= self.capacity;
+        if is_full
+            && !self.statuses.contains_key(&time)
+            && self
+                .statuses
+                .first_key_value()
+                .is_none_or(|(oldest, _)| time < *oldest)
+        {
+            return Err(OutOfOrderStatus {
+                time,
+                newest: self.newest_time().unwrap_or(time),
+            });
+        }
+        self.insert(status);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: i64) -> DateTime<Local> {
+        DateTime::from_timestamp(secs, 0)
+            .expect("Timestamp out of range")
+            .with_timezone(&Local)
+    }
+
+    fn messages(history: &dyn StatusHistory) -> Vec<String> {
+        history
+            .iter()
+            .map(|(_, status)| status.message.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_same_time_deduplicated() {
+        let mut history = HistoryBackend::BTreeMap.new_history(10);
+        for message in ["a", "b"] {
+            history
+                .push_status((at(10), Status::up(message)), OutOfOrderPolicy::Reject)
+                .expect("Statuses observed at the newest time are added");
+        }
+        history
+            .push_status((at(20), Status::up("c")), OutOfOrderPolicy::Reject)
+            .expect("Statuses in order are always added");
+        assert_eq!(messages(history.as_ref()), vec!["b", "c"]);
+        assert_eq!(history.head_index(), 2);
+        assert_eq!(
+            history
+                .iter_since(1)
+                .map(|(time, _)| time)
+                .collect::<Vec<_>>(),
+            vec![at(20)]
+        );
+
+        // Ring buffers keep both.
+        let mut history = HistoryBackend::RingBuffer.new_history(10);
+        for message in ["a", "b"] {
+            history
+                .push_status((at(10), Status::up(message)), OutOfOrderPolicy::Reject)
+                .expect("Statuses observed at the newest time are added");
+        }
+        assert_eq!(messages(history.as_ref()), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_backends_agree() {
+        let histories = [HistoryBackend::RingBuffer, HistoryBackend::BTreeMap].map(|backend| {
+            let mut history = backend.new_history(4);
+            for (secs, status) in [
+                (0, Status::up("a")),
+                (10, Status::down("b")),
+                (20, Status::up("c")),
+                (30, Status::up("d")),
+                (40, Status::down("e")),
+            ] {
+                history
+                    .push_status((at(secs), status), OutOfOrderPolicy::Reject)
+                    .expect("Statuses in order are always added");
+            }
+            history
+                .push_status((at(5), Status::up("f")), OutOfOrderPolicy::Insert)
+                .expect_err("The status would be dropped right away");
+            history
+        });
+        let [ring, map] = &histories;
+        assert_eq!(messages(map.as_ref()), vec!["b", "c", "d", "e"]);
+        assert_eq!(messages(ring.as_ref()), messages(map.as_ref()));
+        assert_eq!(ring.head_index(), map.head_index());
+        let since: Vec<_> = map.iter_since(3).map(|(time, _)| time).collect();
+        assert_eq!(since, vec![at(30), at(40)]);
+        assert_eq!(
+            ring.iter_since(3).map(|(time, _)| time).collect::<Vec<_>>(),
+            since
+        );
+        for (since, until) in [
+            (None, None),
+            (Some(at(15)), Some(at(35))),
+            (Some(at(35)), Some(at(15))),
+        ] {
+            assert_eq!(
+                ring.range(since, until).count(),
+                map.range(since, until).count()
+            );
+            let now = at(50);
+            let max_gap = chrono::Duration::try_seconds(15);
+            assert_eq!(
+                serde_json::to_value(ring.uptime(since, until, now, max_gap)).unwrap(),
+                serde_json::to_value(map.uptime(since, until, now, max_gap)).unwrap()
+            );
+        }
+        assert_eq!(
+            map.status_at(at(25))
+                .map(|(_, status)| status.message.clone()),
+            Some("c".to_string())
+        );
+        assert_eq!(
+            serde_json::to_value(ring.as_ref()).unwrap(),
+            serde_json::to_value(map.as_ref()).unwrap()
+        );
+
+        let converted = HistoryBackend::BTreeMap.convert(ring.clone());
+        assert_eq!(converted.backend(), HistoryBackend::BTreeMap);
+        assert_eq!(messages(converted.as_ref()), messages(ring.as_ref()));
+        assert_eq!(converted.capacity(), 4);
+        assert_eq!(converted.head_index(), ring.head_index());
+    }
+}
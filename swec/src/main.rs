@@ -1,66 +1,216 @@
+use axum::http::{Extensions, HeaderMap, StatusCode, Version};
 use axum::Router;
+use clap::Parser;
+use socket2::{Domain, Protocol, Socket, TcpKeepalive, Type};
 use std::collections::BTreeMap;
 use std::error::Error;
+use std::fs::OpenOptions;
 use std::future::IntoFuture;
-use std::path::Path;
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::{
-    fs::File,
-    io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufWriter, SeekFrom},
     signal::unix::{signal, SignalKind},
-    sync::RwLock,
+    sync::{Mutex, RwLock},
     time::Duration,
 };
-use tower_http::trace::{DefaultMakeSpan, TraceLayer};
+use tower_http::compression::{
+    predicate::{DefaultPredicate, Predicate},
+    CompressionLayer,
+};
+use tower_http::trace::{DefaultMakeSpan, DefaultOnFailure, DefaultOnResponse, TraceLayer};
+use tower_http::LatencyUnit;
+use tracing_subscriber::{
+    filter::{filter_fn, LevelFilter},
+    layer::SubscriberExt,
+    util::SubscriberInitExt,
+    Layer,
+};
 
 mod api;
+mod config;
+mod groups;
+mod history;
+mod persistence;
 mod ringbuffer;
+pub use config::Config;
+use history::HistoryBackend;
+pub use history::{History, StatusHistory};
+use persistence::Persistence;
 pub use ringbuffer::{RingBuffer, StatusRingBuffer};
 use swec_core::{checker, ApiInfo};
 use tracing::{error, info, warn};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// Message of the status added to restored checkers when `restart_status` is enabled.
+const RESTART_MESSAGE: &str = "swec restarted; awaiting fresh data";
+
+#[derive(Parser, Debug)]
+#[command(version, about, author, long_about)]
+struct Args {
+    /// TOML file to read the configuration from. Fields it leaves out keep their default value
+    #[clap(short, long, env = "SWEC_CONFIG")]
+    config: Option<PathBuf>,
+    /// Address of the read-only API
+    #[clap(long)]
+    public_address: Option<String>,
+    /// Address of the read-write API
+    #[clap(long)]
+    private_address: Option<String>,
+    /// Number of statuses kept for each checker
+    #[clap(long)]
+    history_len: Option<usize>,
+    /// Time between two dumps, in seconds
+    #[clap(long)]
+    dump_interval: Option<u64>,
+    /// File the checkers are dumped to and restored from
+    #[clap(long)]
+    dump_path: Option<PathBuf>,
+    /// Where to save the checkers, as `json://<path>`, `msgpack://<path>`, `sqlite://<path>` or
+    /// `s3://<bucket>/<key>`, instead of `persistence` and `dump_path`
+    #[clap(long, value_parser = persistence::Backend::from_url)]
+    storage: Option<(persistence::Backend, PathBuf)>,
+    /// Token that requests to the read-write API must have as a bearer token, except for the
+    /// routes that are also in the read-only API
+    #[clap(long, env = "SWEC_API_TOKEN", hide_env_values = true)]
+    api_token: Option<String>,
+    /// Format of the logs
+    #[clap(long, env = "SWEC_LOG_FORMAT", value_enum)]
+    log_format: Option<config::LogFormat>,
+}
+
+impl Args {
+    /// Override the configuration with the options given on the command line.
+    fn apply(self, config: &mut Config) {
+        if let Some(address) = self.public_address {
+            config.public_address = address;
+        }
+        if let Some(address) = self.private_address {
+            config.private_address = address;
+        }
+        if let Some(history_len) = self.history_len {
+            config.history_len = history_len;
+        }
+        if let Some(interval) = self.dump_interval {
+            config.dump_interval = interval;
+        }
+        if let Some(path) = self.dump_path {
+            config.dump_path = path;
+        }
+        if let Some((backend, path)) = self.storage {
+            config.persistence = backend;
+            config.dump_path = path;
+        }
+        if let Some(token) = self.api_token {
+            config.api_token = Some(token);
+        }
+        if let Some(format) = self.log_format {
+            config.log_format = format;
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // TODO: config file and/or command line arguments
-    let checkers_path = Path::new("swec_dump.json");
-    let history_len = 3600;
-    let truncate_histories = false;
-    let public_address = "127.0.0.1:8080";
-    let private_address = "127.0.0.1:8081";
-    let api_path = "/api/v1";
-    let dump_interval = Duration::from_secs(60);
+    let args = Args::parse();
+    // Logging depends on the configuration, so errors can only be printed as-is here.
+    let mut config = Config::load(args.config.as_deref()).unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(1);
+    });
+    args.apply(&mut config);
+    let config = Arc::new(config);
 
-    tracing_subscriber::fmt::init();
+    // Administrative changes are logged with the "audit" target, which can also be sent to a
+    // dedicated file.
+    let audit_file = config
+        .audit_log
+        .as_ref()
+        .map(|path| OpenOptions::new().create(true).append(true).open(path))
+        .transpose()?;
+    let stdout_layer = tracing_subscriber::fmt::layer();
+    let stdout_layer = match config.log_format {
+        config::LogFormat::Text => stdout_layer.boxed(),
+        config::LogFormat::Json => stdout_layer.json().boxed(),
+    };
+    tracing_subscriber::registry()
+        .with(stdout_layer.with_filter(LevelFilter::INFO))
+        .with(audit_file.map(|file| {
+            let audit_layer = tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(std::sync::Mutex::new(file));
+            match config.log_format {
+                config::LogFormat::Text => audit_layer.boxed(),
+                config::LogFormat::Json => audit_layer.json().boxed(),
+            }
+            .with_filter(filter_fn(|metadata| metadata.target() == "audit"))
+        }))
+        .init();
 
-    info!("Restoring checkers from dump file");
+    info!("Restoring checkers");
 
-    let checkers = restore_checkers(checkers_path, history_len, truncate_histories)
-        .await
-        .unwrap_or_else(|e| {
-            error!("Failed to restore checkers from dump file: {e}, exiting.");
-            error!("The only case where we will allow restoring to fail is if the file is empty, in which case we will just start with no checkers.");
-            std::process::exit(1);
-        });
+    let mut persistence = persistence::from_config(&config);
+    let checkers = restore_checkers(
+        persistence.as_mut(),
+        config.history_backend,
+        config.history_len,
+        config.truncate_histories,
+        config.restart_status,
+    )
+    .await
+    .unwrap_or_else(|e| {
+        error!("Failed to restore checkers: {e}, exiting.");
+        error!("The only case where we will allow restoring to fail is if the dump file is empty or does not exist, in which case we will just start with no checkers.");
+        std::process::exit(1);
+    });
+    let records_statuses = persistence.records_statuses();
+    let persistence = Arc::new(Mutex::new(persistence));
 
-    let state_writer = tokio::fs::OpenOptions::new()
-        .write(true)
-        .create(true)
-        .truncate(false)
-        .open(checkers_path)
-        .await?;
+    let mut app_state = api::AppState::new(
+        checkers,
+        config.history_backend,
+        config.history_len,
+        config
+            .history_max_age
+            .and_then(|secs| chrono::Duration::try_seconds(i64::try_from(secs).ok()?)),
+        api::Damping {
+            statuses: config.transition_damping_statuses,
+            duration: config.transition_damping_secs.map(Duration::from_secs),
+        },
+        config.channel_capacity,
+        config.global_channel_capacity,
+    );
+    let recorder = records_statuses.then(|| {
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        app_state.record_history_changes(sender);
+        tokio::spawn(recorder_task(receiver, persistence.clone()))
+    });
+    let app_state = Arc::new(RwLock::new(app_state));
 
-    let app_state = Arc::new(RwLock::new(api::AppState::new(checkers, history_len)));
-
-    let public_server = make_server(false, app_state.clone(), public_address, api_path).await?;
-    let private_server = make_server(true, app_state.clone(), private_address, api_path).await?;
+    let public_server = make_server(
+        false,
+        config.clone(),
+        app_state.clone(),
+        &config.public_address,
+    )
+    .await?;
+    let private_server = make_server(
+        true,
+        config.clone(),
+        app_state.clone(),
+        &config.private_address,
+    )
+    .await?;
     let dumper = {
         let app_state = app_state.clone();
-        let writer = BufWriter::new(state_writer.try_clone().await?);
-        tokio::spawn(dumper_task(app_state, writer, dump_interval))
+        let persistence = persistence.clone();
+        let interval = Duration::from_secs(config.dump_interval);
+        let splay = Duration::from_secs(config.dump_splay);
+        tokio::spawn(dumper_task(app_state, persistence, interval, splay))
     };
 
+    let backlog_monitor = tokio::spawn(backlog_monitor_task(app_state.clone()));
+
     info!("Starting servers");
 
     // Wait for a server to shut down or for a stop signal to be received.
@@ -69,16 +219,31 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         v = public_server => result_to_server_end_message(v),
         v = private_server => result_to_server_end_message(v),
         _ = dumper => unreachable!(),
+        _ = backlog_monitor => unreachable!(),
         () = wait_for_stop_signal() => "Interrupt received".to_string(),
     };
 
     info!("{end_message}");
 
-    // Save the checkers to file before exiting
-    dump_checkers(&app_state, &mut BufWriter::new(state_writer))
+    // Give websocket clients some time to close their connections before exiting.
+    let shutdown = app_state.read().await.shut_down();
+    let grace_period = Duration::from_secs(config.shutdown_grace_period);
+    if tokio::time::timeout(grace_period, shutdown.closed())
+        .await
+        .is_err()
+    {
+        warn!("Some websockets were still open after {grace_period:?}, closing them anyway");
+    }
+
+    // Save the checkers before exiting, whole so that changes that were not recorded yet are
+    // saved too.
+    if let Some(recorder) = recorder {
+        recorder.abort();
+    }
+    dump_checkers(&app_state, &persistence, true)
         .await
         .unwrap_or_else(|e| {
-            warn!("Failed to dump checkers to file: {e}");
+            warn!("Failed to dump checkers: {e}");
         });
 
     Ok(())
@@ -86,9 +251,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
 async fn make_server(
     can_write: bool,
+    config: Arc<Config>,
     app_state: Arc<RwLock<api::AppState>>,
     address: &str,
-    api_path: &str,
 ) -> Result<
     impl core::future::Future<Output = std::result::Result<(), std::io::Error>>,
     std::io::Error,
@@ -99,20 +264,79 @@ async fn make_server(
     };
     let router = Router::new()
         .nest(
-            api_path,
+            &config.api_path,
             if can_write {
-                api::read_write_router()
+                api::read_write_router(&config)
             } else {
                 api::read_only_router()
             },
         )
-        .with_state((api_info, app_state))
+        .with_state((api_info, config.clone(), app_state))
+        // Histories are large and compress well. Websocket handshakes are left alone, since their
+        // response is an upgrade rather than a body.
         .layer(
+            CompressionLayer::new().compress_when(DefaultPredicate::new().and(
+                |status: StatusCode, _: Version, _: &HeaderMap, _: &Extensions| {
+                    status != StatusCode::SWITCHING_PROTOCOLS
+                },
+            )),
+        )
+        .layer({
+            // The span has the method and URI of the request, which the response event inherits.
+            let level = if config.access_log {
+                tracing::Level::INFO
+            } else {
+                tracing::Level::DEBUG
+            };
             TraceLayer::new_for_http()
-                .make_span_with(DefaultMakeSpan::default().include_headers(true)),
-        );
-    let listener = tokio::net::TcpListener::bind(address).await?;
-    Ok(axum::serve(listener, router.into_make_service()).into_future())
+                .make_span_with(
+                    DefaultMakeSpan::new()
+                        .level(level)
+                        .include_headers(config.access_log_headers),
+                )
+                .on_response(
+                    DefaultOnResponse::new()
+                        .level(level)
+                        .latency_unit(LatencyUnit::Micros),
+                )
+                .on_failure(DefaultOnFailure::new().latency_unit(LatencyUnit::Micros))
+        });
+    let listener = bind(address, &config).await?;
+    // The address of clients is given to handlers, for the audit log.
+    Ok(axum::serve(
+        listener,
+        router.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .into_future())
+}
+
+/// Bind a listener to the address with the TCP tuning of the configuration.
+async fn bind(address: &str, config: &Config) -> Result<tokio::net::TcpListener, std::io::Error> {
+    let address = tokio::net::lookup_host(address)
+        .await?
+        .next()
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("{address} does not resolve to any address"),
+            )
+        })?;
+    let socket = Socket::new(
+        Domain::for_address(address),
+        Type::STREAM,
+        Some(Protocol::TCP),
+    )?;
+    // Same as what tokio::net::TcpListener::bind does, so that restarting doesn't fail while old
+    // connections are in TIME_WAIT.
+    socket.set_reuse_address(true)?;
+    // Accepted connections inherit these options from the listener.
+    if let Some(keepalive) = config.tcp_keepalive {
+        socket.set_tcp_keepalive(&TcpKeepalive::new().with_time(Duration::from_secs(keepalive)))?;
+    }
+    socket.set_nonblocking(true)?;
+    socket.bind(&address.into())?;
+    socket.listen(i32::try_from(config.listen_backlog).unwrap_or(i32::MAX))?;
+    tokio::net::TcpListener::from_std(socket.into())
 }
 
 /// Wait for a stop signal to be received.
@@ -140,22 +364,36 @@ async fn wait_for_stop_signal() {
     futures::future::select_all(interrupt_futures).await;
 }
 
+/// Save the checkers, only with their specs if the backend records statuses as they are added,
+/// unless `whole` is true.
 async fn dump_checkers(
     app_state: &Arc<RwLock<api::AppState>>,
-    writer: &mut BufWriter<File>,
-) -> Result<(), Box<dyn Error>> {
-    info!("Saving checkers to file");
-    let serialized = app_state.read().await.checkers_to_json()?;
-    (*writer).seek(SeekFrom::Start(0)).await?; // super important, otherwise we just append to the file
-    (*writer).write_all(serialized.as_bytes()).await?;
-    (*writer).flush().await?;
-    Ok(())
+    persistence: &Mutex<Box<dyn Persistence>>,
+    whole: bool,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    info!("Saving checkers");
+    // Locked first, so that no change is recorded between reading the state and saving it.
+    let mut persistence = persistence.lock().await;
+    let result = if persistence.records_statuses() && !whole {
+        let specs = app_state.read().await.get_saved_specs();
+        persistence.save_specs(&specs).await
+    } else {
+        let checkers = app_state.read().await.get_checkers();
+        persistence.save(&checkers).await
+    };
+    drop(persistence);
+    app_state
+        .write()
+        .await
+        .record_dump(result.as_ref().copied().map_err(ToString::to_string));
+    result
 }
 
 async fn dumper_task(
     app_state: Arc<RwLock<api::AppState>>,
-    mut writer: BufWriter<File>,
+    persistence: Arc<Mutex<Box<dyn Persistence>>>,
     interval: Duration,
+    splay: Duration,
 ) -> ! {
     let make_signal =
         || signal(SignalKind::user_defined1()).expect("Failed to create signal for dumper task");
@@ -168,51 +406,97 @@ async fn dumper_task(
                     warn!("Cannot receive signals from this channel anymore, creating a new one");
                     s = make_signal();
                 }
-                info!("Received SIGUSR1, dumping checkers to file");
+                info!("Received SIGUSR1, dumping checkers");
             }
-            () = tokio::time::sleep(interval) => {}
+            () = tokio::time::sleep(interval + splay.mul_f64(rand::random())) => {}
         };
-        dump_checkers(&app_state, &mut writer)
+        dump_checkers(&app_state, &persistence, false)
             .await
             .unwrap_or_else(|e| {
-                warn!("Failed to dump checkers to file: {e}");
+                warn!("Failed to dump checkers: {e}");
             });
     }
 }
 
+/// Save changes to histories as they happen, for persistence backends that record them.
+/// Changes that arrive while others are being saved are saved together.
+async fn recorder_task(
+    mut changes: tokio::sync::mpsc::UnboundedReceiver<persistence::HistoryChange>,
+    persistence: Arc<Mutex<Box<dyn Persistence>>>,
+) {
+    while let Some(change) = changes.recv().await {
+        let mut batch = vec![change];
+        while let Ok(change) = changes.try_recv() {
+            batch.push(change);
+        }
+        if let Err(e) = persistence.lock().await.record(&batch).await {
+            warn!(
+                "Failed to record {} changes to histories: {e}, they will be saved with the next dump",
+                batch.len()
+            );
+        }
+    }
+}
+
+/// Periodically warn about slow websocket clients, which would otherwise only show once they lag.
+async fn backlog_monitor_task(app_state: Arc<RwLock<api::AppState>>) -> ! {
+    let mut interval = tokio::time::interval(Duration::from_secs(5));
+    loop {
+        interval.tick().await;
+        app_state.read().await.warn_about_backlogs();
+    }
+}
+
 async fn restore_checkers(
-    path: &Path,
+    persistence: &mut dyn Persistence,
+    history_backend: HistoryBackend,
     history_length: usize,
     truncate: bool,
-) -> Result<BTreeMap<String, checker::Checker<StatusRingBuffer>>, Box<dyn Error>> {
-    let mut file = tokio::fs::File::open(path).await?;
-    let mut contents = Vec::new();
-    file.read_to_end(&mut contents).await?;
-
-    if contents.is_empty() {
-        // We can safely say that the user has just cleared the file or just installed swec,
-        // which means we can return an empty map.
-        return Ok(BTreeMap::new());
+    restart_status: bool,
+) -> Result<BTreeMap<String, checker::Checker<History>>, Box<dyn Error + Send + Sync>> {
+    // The user might have changed the history backend or length between dumping and restoring.
+    let mut restored: BTreeMap<_, _> = persistence
+        .load()
+        .await?
+        .into_iter()
+        .map(|(name, checker)| {
+            let statuses = history_backend.convert(checker.statuses);
+            (name, checker::Checker::new(checker.spec, statuses))
+        })
+        .collect();
+    for (name, checker) in &mut restored {
+        let history_length = checker.spec.history_len_or(history_length);
+        let statuses = &mut checker.statuses;
+        if statuses.capacity() == history_length {
+            continue;
+        }
+        if truncate || statuses.len() <= history_length {
+            statuses.truncate_fifo(history_length);
+        } else {
+            warn!(
+                "History of {name} has {} statuses, more than the history length of {history_length}. Keeping them all; enable truncate_histories to drop the oldest.",
+                statuses.len()
+            );
+            statuses.truncate_fifo(statuses.len());
+        }
     }
 
-    let mut deserialized: BTreeMap<String, checker::Checker<StatusRingBuffer>> =
-        serde_json::from_slice(&contents)?;
-
-    // Make sure the histories all have the correct length, since deserializing a ring buffer
-    // doesn't guarantee that the history will be the correct length, plus the user might have
-    // changed the history length between dumping and restoring.
-    for checker in deserialized.values_mut() {
-        if truncate {
-            checker.statuses.truncate_fifo(history_length);
-        } else {
-            checker
-                .statuses
-                .resize(history_length)
-                .expect("Failed to resize checker history");
+    if restart_status {
+        let now = chrono::Local::now();
+        for checker in restored.values_mut() {
+            if !checker.spec.enabled {
+                continue;
+            }
+            // Keep the last known state so that restarting doesn't look like a transition.
+            let Some((_, latest)) = checker.statuses.iter().next_back() else {
+                continue;
+            };
+            let status = checker::Status::up(RESTART_MESSAGE).with_health(latest.health);
+            checker.statuses.push((now, status));
         }
     }
 
-    Ok(deserialized)
+    Ok(restored)
 }
 
 fn result_to_server_end_message(r: Result<(), std::io::Error>) -> String {
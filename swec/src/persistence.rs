@@ -0,0 +1,312 @@
+use crate::{Config, History, StatusRingBuffer};
+use async_trait::async_trait;
+use chrono::{DateTime, Local};
+use rusty_s3::Credentials;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use swec_core::checker;
+use tracing::{info, warn};
+
+mod s3;
+mod sqlite;
+pub use s3::ObjectStoragePersistence;
+pub use sqlite::SqlitePersistence;
+
+pub type Checkers = BTreeMap<String, checker::Checker<History>>;
+
+/// Everything saved about a checker but its statuses, for backends that record statuses as they
+/// are added.
+#[derive(Debug, Clone)]
+pub struct SavedSpec {
+    pub spec: checker::Spec,
+    /// Capacity of the history
+    pub capacity: usize,
+    /// Head of the history, see `RingBuffer::head`
+    pub head: u64,
+    /// Time of the oldest status kept, older ones having been dropped from the history
+    pub oldest: Option<DateTime<Local>>,
+}
+
+/// A change to the history of a checker, recorded as it happens by backends that support it.
+#[derive(Debug, Clone)]
+pub enum HistoryChange {
+    /// A status was added to the history, which then had the given head
+    Added {
+        checker: String,
+        status: (DateTime<Local>, checker::Status),
+        head: u64,
+    },
+    /// The history was cleared
+    Cleared { checker: String },
+    /// The checker was deleted
+    Removed { checker: String },
+}
+
+/// A checker as stored in dumps.
+/// Unlike `checker::Checker`, whose serialization is shared with the API, this keeps the
+/// capacity of the history. Dumps from before it was kept are still read, with the capacity
+/// set to the length of the history.
+#[derive(Serialize, Deserialize)]
+struct StoredChecker<Spec, Statuses> {
+    spec: Spec,
+    statuses: Statuses,
+    /// The head of the history, so that the indices of statuses are kept across restarts. Dumps
+    /// from before it was kept start over from the length of the history.
+    #[serde(default)]
+    head: Option<u64>,
+}
+
+/// Borrow the checkers in their stored form, to save them without cloning.
+fn to_stored(checkers: &Checkers) -> BTreeMap<&String, StoredChecker<&checker::Spec, &History>> {
+    checkers
+        .iter()
+        .map(|(name, checker)| {
+            (
+                name,
+                StoredChecker {
+                    spec: &checker.spec,
+                    statuses: &checker.statuses,
+                    head: Some(checker.statuses.head_index()),
+                },
+            )
+        })
+        .collect()
+}
+
+/// Histories of all backends are stored like ring buffers, so they are restored as such, to be
+/// moved to the configured backend by the caller.
+fn from_stored(
+    stored: BTreeMap<String, StoredChecker<checker::Spec, StatusRingBuffer>>,
+) -> Checkers {
+    stored
+        .into_iter()
+        .map(
+            |(
+                name,
+                StoredChecker {
+                    spec,
+                    mut statuses,
+                    head,
+                },
+            )| {
+                if let Some(head) = head {
+                    statuses.set_head(head);
+                }
+                let statuses: History = Box::new(statuses);
+                (name, checker::Checker::new(spec, statuses))
+            },
+        )
+        .collect()
+}
+
+/// Where checkers are saved to and restored from.
+#[async_trait]
+pub trait Persistence: Send {
+    /// Save the checkers, replacing the previously saved ones.
+    async fn save(&mut self, checkers: &Checkers) -> Result<(), Box<dyn Error + Send + Sync>>;
+
+    /// Load the last saved checkers, or no checkers if nothing was saved yet.
+    async fn load(&mut self) -> Result<Checkers, Box<dyn Error + Send + Sync>>;
+
+    /// Whether statuses are currently saved as they are added, with `record`, in which case the
+    /// periodic saves only need the specs, through `save_specs`. When this is false, `save` must
+    /// be used to save the statuses.
+    fn records_statuses(&self) -> bool {
+        false
+    }
+
+    /// Save changes to histories as they happen. Backends that don't record statuses ignore them.
+    async fn record(
+        &mut self,
+        _changes: &[HistoryChange],
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        Ok(())
+    }
+
+    /// Save everything but the statuses, and drop those older than the oldest kept ones, with
+    /// the same checkers as `save` would. Only called when `records_statuses` is true.
+    async fn save_specs(
+        &mut self,
+        _specs: &BTreeMap<String, SavedSpec>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        Err("This backend can only save whole checkers".into())
+    }
+}
+
+/// The persistence backends that can be selected in the configuration.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Backend {
+    /// A single JSON file at `dump_path`
+    #[default]
+    FileJson,
+    /// A single MessagePack file at `dump_path`, which is more compact than JSON
+    FileMsgpack,
+    /// A SQLite database at `dump_path`, which is updated incrementally instead of rewritten on
+    /// each save
+    Sqlite,
+    /// A single JSON object in an S3 bucket, or any store with an S3-compatible API, with
+    /// `dump_path` giving its location as `<bucket>/<key>`. See the `s3_*` settings for the
+    /// endpoint and the credentials.
+    S3,
+}
+
+impl Backend {
+    /// Parse a storage URL such as `sqlite:///var/lib/swec/swec.db` into the backend and its path.
+    /// The schemes are `json`, `msgpack`, `sqlite` and `s3`, the latter as `s3://<bucket>/<key>`.
+    pub fn from_url(url: &str) -> Result<(Self, PathBuf), String> {
+        let (scheme, path) = url
+            .split_once("://")
+            .ok_or_else(|| format!("Invalid storage {url}, expected <scheme>://<path>"))?;
+        let backend = match scheme {
+            "json" => Self::FileJson,
+            "msgpack" => Self::FileMsgpack,
+            "sqlite" => Self::Sqlite,
+            "s3" => Self::S3,
+            _ => return Err(format!("Unknown storage scheme {scheme}")),
+        };
+        if path.is_empty() {
+            return Err(format!("Invalid storage {url}, the path is empty"));
+        }
+        Ok((backend, PathBuf::from(path)))
+    }
+}
+
+/// Create the persistence backend selected in the configuration.
+pub fn from_config(config: &Config) -> Box<dyn Persistence> {
+    match config.persistence {
+        Backend::FileJson => Box::new(FilePersistence::new(
+            config.dump_path.clone(),
+            if config.pretty_dump {
+                FileFormat::PrettyJson
+            } else {
+                FileFormat::Json
+            },
+        )),
+        Backend::FileMsgpack => Box::new(FilePersistence::new(
+            config.dump_path.clone(),
+            FileFormat::MessagePack,
+        )),
+        Backend::Sqlite => Box::new(SqlitePersistence::new(config.dump_path.clone())),
+        Backend::S3 => Box::new(ObjectStoragePersistence::new(
+            config.dump_path.clone(),
+            config
+                .s3_endpoint
+                .clone()
+                .unwrap_or_else(|| format!("https://s3.{}.amazonaws.com", config.s3_region)),
+            config.s3_region.clone(),
+            match (&config.s3_access_key_id, &config.s3_secret_access_key) {
+                (Some(key), Some(secret)) => Some(Credentials::new(key, secret)),
+                _ => Credentials::from_env(),
+            },
+        )),
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum FileFormat {
+    Json,
+    /// Indented JSON, for humans to inspect the dump
+    PrettyJson,
+    MessagePack,
+}
+
+/// Saves all checkers to a single file, rewriting it entirely on each save.
+///
+/// Each save is written to a temporary file next to it, which then replaces it, so that a crash or
+/// a full disk in the middle of a save leaves the previous dump intact.
+pub struct FilePersistence {
+    path: PathBuf,
+    format: FileFormat,
+}
+
+impl FilePersistence {
+    pub const fn new(path: PathBuf, format: FileFormat) -> Self {
+        Self { path, format }
+    }
+
+    /// The file saves are written to before replacing the dump, e.g. `swec_dump.json.tmp`.
+    fn tmp_path(&self) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(".tmp");
+        PathBuf::from(path)
+    }
+
+    fn parse(&self, contents: &[u8]) -> Result<Checkers, Box<dyn Error + Send + Sync>> {
+        if contents.is_empty() {
+            // We can safely say that the user has just cleared the file or just installed swec,
+            // which means we can return an empty map.
+            return Ok(BTreeMap::new());
+        }
+
+        Ok(from_stored(match self.format {
+            FileFormat::Json | FileFormat::PrettyJson => serde_json::from_slice(contents)?,
+            FileFormat::MessagePack => rmp_serde::from_slice(contents)?,
+        }))
+    }
+}
+
+#[async_trait]
+impl Persistence for FilePersistence {
+    async fn save(&mut self, checkers: &Checkers) -> Result<(), Box<dyn Error + Send + Sync>> {
+        // Serialize straight to the file instead of building the whole dump in memory first,
+        // since histories can be large. This is blocking, so let the runtime know.
+        let checkers = to_stored(checkers);
+        let tmp_path = self.tmp_path();
+        tokio::task::block_in_place(|| {
+            let mut writer = BufWriter::new(std::fs::File::create(&tmp_path)?);
+            match self.format {
+                FileFormat::Json => serde_json::to_writer(&mut writer, &checkers)?,
+                FileFormat::PrettyJson => serde_json::to_writer_pretty(&mut writer, &checkers)?,
+                FileFormat::MessagePack => rmp_serde::encode::write_named(&mut writer, &checkers)?,
+            }
+            writer.flush()?;
+            // Make sure the new dump is on disk before it replaces the old one, otherwise a power
+            // loss could leave neither.
+            writer.get_ref().sync_all()?;
+            // Atomic as long as both are on the same filesystem, which they are since they are in
+            // the same directory.
+            std::fs::rename(&tmp_path, &self.path)?;
+            Ok(())
+        })
+    }
+
+    async fn load(&mut self) -> Result<Checkers, Box<dyn Error + Send + Sync>> {
+        let contents = match tokio::fs::read(&self.path).await {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                // Same as an empty file: this is a fresh install. Create the file now so that
+                // problems writing to it show up on startup.
+                info!("Dump file does not exist yet, starting with no checkers");
+                tokio::fs::File::create(&self.path).await?;
+                return Ok(BTreeMap::new());
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        match self.parse(&contents) {
+            Ok(checkers) => Ok(checkers),
+            Err(e) => {
+                // The dump may have been written in place by an older version that crashed midway,
+                // in which case a save that didn't get to replace it may still be around.
+                let tmp_path = self.tmp_path();
+                let Ok(tmp_contents) = tokio::fs::read(&tmp_path).await else {
+                    return Err(e);
+                };
+                match self.parse(&tmp_contents) {
+                    Ok(checkers) => {
+                        warn!(
+                            "Dump file is corrupt ({e}), restoring from {} instead",
+                            tmp_path.display()
+                        );
+                        Ok(checkers)
+                    }
+                    Err(_) => Err(e),
+                }
+            }
+        }
+    }
+}
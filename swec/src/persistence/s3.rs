@@ -0,0 +1,128 @@
+use super::{from_stored, to_stored, Checkers, Persistence};
+use async_trait::async_trait;
+use rusty_s3::{Bucket, Credentials, S3Action, UrlStyle};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::info;
+
+/// Time the signed URL of each request is valid for, which only needs to cover sending it.
+const SIGNATURE_VALIDITY: Duration = Duration::from_secs(60);
+
+/// Saves all checkers as a single JSON object in an S3 bucket, or any store with an S3-compatible
+/// API, rewriting it entirely on each save.
+///
+/// Objects are replaced atomically by the store, so a failed save leaves the previous dump intact.
+pub struct ObjectStoragePersistence {
+    /// `<bucket>/<key>` of the object
+    location: PathBuf,
+    /// Endpoint of the store, e.g. `https://s3.eu-west-1.amazonaws.com`
+    endpoint: String,
+    region: String,
+    credentials: Option<Credentials>,
+    client: reqwest::Client,
+}
+
+impl ObjectStoragePersistence {
+    /// Save to the object at `location`, given as `<bucket>/<key>`. Requests are anonymous
+    /// without credentials, which only works with public buckets.
+    pub fn new(
+        location: PathBuf,
+        endpoint: String,
+        region: String,
+        credentials: Option<Credentials>,
+    ) -> Self {
+        Self {
+            location,
+            endpoint,
+            region,
+            credentials,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// The bucket and the key of the object in it. Invalid locations and endpoints are only
+    /// reported on first use, like other backends that fail to open their storage.
+    fn object(&self) -> Result<(Bucket, String), Box<dyn Error + Send + Sync>> {
+        let location = self.location.to_string_lossy();
+        let (bucket, key) = location
+            .split_once('/')
+            .filter(|(bucket, key)| !bucket.is_empty() && !key.is_empty())
+            .ok_or_else(|| {
+                format!("Invalid object location {location}, expected <bucket>/<key>")
+            })?;
+        // Path-style URLs work with every S3-compatible store, unlike virtual-hosted ones.
+        let bucket = Bucket::new(
+            self.endpoint.parse()?,
+            UrlStyle::Path,
+            bucket.to_string(),
+            self.region.clone(),
+        )?;
+        Ok((bucket, key.to_string()))
+    }
+}
+
+#[async_trait]
+impl Persistence for ObjectStoragePersistence {
+    async fn save(&mut self, checkers: &Checkers) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let (bucket, key) = self.object()?;
+        let url = bucket
+            .put_object(self.credentials.as_ref(), &key)
+            .sign(SIGNATURE_VALIDITY);
+        let body = serde_json::to_vec(&to_stored(checkers))?;
+        self.client
+            .put(url)
+            .body(body)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    async fn load(&mut self) -> Result<Checkers, Box<dyn Error + Send + Sync>> {
+        let (bucket, key) = self.object()?;
+        let url = bucket
+            .get_object(self.credentials.as_ref(), &key)
+            .sign(SIGNATURE_VALIDITY);
+        let response = self.client.get(url).send().await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            info!("Dump object does not exist yet, starting with no checkers");
+            return Ok(BTreeMap::new());
+        }
+        let contents = response.error_for_status()?.bytes().await?;
+        if contents.is_empty() {
+            return Ok(BTreeMap::new());
+        }
+        Ok(from_stored(serde_json::from_slice(&contents)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn persistence(location: &str) -> ObjectStoragePersistence {
+        ObjectStoragePersistence::new(
+            PathBuf::from(location),
+            "http://localhost:9000".to_string(),
+            "us-east-1".to_string(),
+            None,
+        )
+    }
+
+    #[test]
+    fn test_object() {
+        let (bucket, key) = persistence("swec/dumps/swec_dump.json").object().unwrap();
+        assert_eq!(bucket.name(), "swec");
+        assert_eq!(key, "dumps/swec_dump.json");
+        assert_eq!(
+            bucket.object_url(&key).unwrap().as_str(),
+            "http://localhost:9000/swec/dumps/swec_dump.json"
+        );
+
+        assert!(persistence("swec_dump.json").object().is_err());
+        assert!(persistence("swec/").object().is_err());
+        assert!(persistence("/swec_dump.json").object().is_err());
+    }
+}
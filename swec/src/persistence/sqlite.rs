@@ -0,0 +1,497 @@
+use super::{Checkers, HistoryChange, Persistence, SavedSpec};
+use crate::history::{Entry, History, StatusHistory};
+use crate::StatusRingBuffer;
+use async_trait::async_trait;
+use chrono::{DateTime, Local, SecondsFormat};
+use rusqlite::{params, Connection, Transaction};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::error::Error;
+use std::path::PathBuf;
+use swec_core::{checker, Health};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS specs (
+    checker TEXT PRIMARY KEY,
+    description TEXT NOT NULL,
+    url TEXT,
+    \"group\" TEXT,
+    enabled INTEGER NOT NULL,
+    channel_capacity INTEGER,
+    history_capacity INTEGER NOT NULL,
+    tags TEXT NOT NULL DEFAULT '',
+    history_head INTEGER,
+    history_len INTEGER
+);
+CREATE TABLE IF NOT EXISTS statuses (
+    checker TEXT NOT NULL,
+    time TEXT NOT NULL,
+    is_up INTEGER NOT NULL,
+    message TEXT NOT NULL,
+    health TEXT NOT NULL,
+    response_time_ns INTEGER,
+    maintenance INTEGER NOT NULL DEFAULT 0
+);
+CREATE INDEX IF NOT EXISTS statuses_by_checker ON statuses (checker, time);
+";
+
+/// Saves checkers to a SQLite database, with a row per status.
+/// Statuses are recorded as they are added, so that a crash loses none of them, and the periodic
+/// saves only write the specs and delete the statuses dropped from the histories. Each write is
+/// done in a transaction so that a crash midway leaves the previous one intact.
+pub struct SqlitePersistence {
+    path: PathBuf,
+    connection: Option<Connection>,
+    /// Whether recording changes failed since the last full save, in which case the statuses are
+    /// saved whole on the next one instead
+    out_of_sync: bool,
+}
+
+impl SqlitePersistence {
+    pub const fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            connection: None,
+            out_of_sync: false,
+        }
+    }
+
+    /// Open the database on first use, creating the tables if needed.
+    fn connection(&mut self) -> rusqlite::Result<&mut Connection> {
+        if self.connection.is_none() {
+            let connection = Connection::open(&self.path)?;
+            connection.execute_batch(SCHEMA)?;
+            migrate(&connection)?;
+            self.connection = Some(connection);
+        }
+        Ok(self
+            .connection
+            .as_mut()
+            .expect("The connection was just opened"))
+    }
+}
+
+/// Add the columns missing from databases created by older versions.
+fn migrate(connection: &Connection) -> rusqlite::Result<()> {
+    for (table, column, definition) in [
+        ("specs", "tags", "TEXT NOT NULL DEFAULT ''"),
+        ("specs", "history_head", "INTEGER"),
+        ("specs", "history_len", "INTEGER"),
+        ("statuses", "maintenance", "INTEGER NOT NULL DEFAULT 0"),
+    ] {
+        let exists = connection
+            .prepare("SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2")?
+            .exists(params![table, column])?;
+        if !exists {
+            connection.execute_batch(&format!(
+                "ALTER TABLE {table} ADD COLUMN {column} {definition}"
+            ))?;
+        }
+    }
+    Ok(())
+}
+
+/// Format times so that their text sorts like the times themselves.
+fn format_time(time: DateTime<Local>) -> String {
+    time.to_utc().to_rfc3339_opts(SecondsFormat::Nanos, true)
+}
+
+const fn health_name(health: Health) -> &'static str {
+    match health {
+        Health::Up => "up",
+        Health::Degraded => "degraded",
+        Health::Down => "down",
+    }
+}
+
+fn parse_health(name: &str) -> Result<Health, String> {
+    match name {
+        "up" => Ok(Health::Up),
+        "degraded" => Ok(Health::Degraded),
+        "down" => Ok(Health::Down),
+        _ => Err(format!("Unknown health in the database: {name}")),
+    }
+}
+
+fn insert_status(tx: &Transaction, name: &str, (time, status): Entry) -> rusqlite::Result<()> {
+    let response_time = status
+        .response_time
+        .map(|d| i64::try_from(d.as_nanos()).unwrap_or(i64::MAX));
+    tx.execute(
+        "INSERT INTO statuses (checker, time, is_up, message, health, response_time_ns, maintenance)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            name,
+            format_time(time),
+            status.is_up(),
+            status.message,
+            health_name(status.health),
+            response_time,
+            status.maintenance
+        ],
+    )?;
+    Ok(())
+}
+
+/// Bring the stored statuses of a checker in line with its history.
+fn sync_statuses(
+    tx: &Transaction,
+    name: &str,
+    statuses: &dyn StatusHistory,
+) -> rusqlite::Result<()> {
+    // Statuses dropped from the front of the history.
+    match statuses.iter().next() {
+        Some((oldest, _)) => tx.execute(
+            "DELETE FROM statuses WHERE checker = ?1 AND time < ?2",
+            params![name, format_time(oldest)],
+        )?,
+        None => tx.execute("DELETE FROM statuses WHERE checker = ?1", params![name])?,
+    };
+    let newest: Option<String> = tx.query_row(
+        "SELECT MAX(time) FROM statuses WHERE checker = ?1",
+        params![name],
+        |row| row.get(0),
+    )?;
+    let mut inserted = 0;
+    for status in statuses.iter() {
+        if newest
+            .as_ref()
+            .is_none_or(|newest| format_time(status.0) > *newest)
+        {
+            insert_status(tx, name, status)?;
+            inserted += 1;
+        }
+    }
+    // Statuses inserted before the newest one, which are allowed by the out-of-order policy,
+    // aren't caught above: rewrite the history whenever the counts don't match.
+    let stored: usize = tx.query_row(
+        "SELECT COUNT(*) FROM statuses WHERE checker = ?1",
+        params![name],
+        |row| row.get(0),
+    )?;
+    if stored != statuses.len() {
+        tx.execute("DELETE FROM statuses WHERE checker = ?1", params![name])?;
+        for status in statuses.iter() {
+            insert_status(tx, name, status)?;
+        }
+    } else if inserted > 0 {
+        tracing::debug!("Saved {inserted} new statuses of {name}");
+    }
+    Ok(())
+}
+
+/// Delete the checkers that aren't in the given ones, with their statuses.
+fn delete_others<T>(tx: &Transaction, checkers: &BTreeMap<String, T>) -> rusqlite::Result<()> {
+    let stored: BTreeSet<String> = tx
+        .prepare("SELECT checker FROM specs")?
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    for name in stored.iter().filter(|name| !checkers.contains_key(*name)) {
+        tx.execute("DELETE FROM specs WHERE checker = ?1", params![name])?;
+        tx.execute("DELETE FROM statuses WHERE checker = ?1", params![name])?;
+    }
+    Ok(())
+}
+
+fn upsert_spec(
+    tx: &Transaction,
+    name: &str,
+    spec: &checker::Spec,
+    capacity: usize,
+    head: u64,
+) -> rusqlite::Result<()> {
+    tx.execute(
+        "INSERT INTO specs
+             (checker, description, url, \"group\", enabled, channel_capacity, history_capacity,
+              tags, history_head, history_len)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+         ON CONFLICT (checker) DO UPDATE SET
+             description = excluded.description,
+             url = excluded.url,
+             \"group\" = excluded.\"group\",
+             enabled = excluded.enabled,
+             channel_capacity = excluded.channel_capacity,
+             history_capacity = excluded.history_capacity,
+             tags = excluded.tags,
+             history_head = excluded.history_head,
+             history_len = excluded.history_len",
+        params![
+            name,
+            spec.description,
+            spec.url,
+            spec.group,
+            spec.enabled,
+            spec.channel_capacity,
+            capacity,
+            // Tags can't contain commas, see `Spec::validate`.
+            spec.tags.join(","),
+            // SQLite integers are signed, but it would take centuries to overflow them.
+            i64::try_from(head).unwrap_or(i64::MAX),
+            spec.history_len
+        ],
+    )?;
+    Ok(())
+}
+
+fn save(connection: &mut Connection, checkers: &Checkers) -> rusqlite::Result<()> {
+    let tx = connection.transaction()?;
+    delete_others(&tx, checkers)?;
+    for (name, checker) in checkers {
+        upsert_spec(
+            &tx,
+            name,
+            &checker.spec,
+            checker.statuses.capacity(),
+            checker.statuses.head_index(),
+        )?;
+        sync_statuses(&tx, name, checker.statuses.as_ref())?;
+    }
+    tx.commit()
+}
+
+fn save_specs(
+    connection: &mut Connection,
+    specs: &BTreeMap<String, SavedSpec>,
+) -> rusqlite::Result<()> {
+    let tx = connection.transaction()?;
+    delete_others(&tx, specs)?;
+    for (name, saved) in specs {
+        upsert_spec(&tx, name, &saved.spec, saved.capacity, saved.head)?;
+        // Statuses dropped from the front of the history, which aren't recorded one by one.
+        match saved.oldest {
+            Some(oldest) => tx.execute(
+                "DELETE FROM statuses WHERE checker = ?1 AND time < ?2",
+                params![name, format_time(oldest)],
+            )?,
+            None => tx.execute("DELETE FROM statuses WHERE checker = ?1", params![name])?,
+        };
+    }
+    // Statuses recorded for checkers that were deleted before any save had their spec.
+    tx.execute(
+        "DELETE FROM statuses WHERE checker NOT IN (SELECT checker FROM specs)",
+        [],
+    )?;
+    tx.commit()
+}
+
+fn record(connection: &mut Connection, changes: &[HistoryChange]) -> rusqlite::Result<()> {
+    let tx = connection.transaction()?;
+    for change in changes {
+        match change {
+            HistoryChange::Added {
+                checker,
+                status,
+                head,
+            } => {
+                insert_status(&tx, checker, (status.0, &status.1))?;
+                // Keeps the indices of statuses right if the server stops before the next save.
+                // A save may have written a later head already, if it ran before this was
+                // recorded.
+                tx.execute(
+                    "UPDATE specs SET history_head = MAX(history_head, ?2) WHERE checker = ?1",
+                    params![checker, i64::try_from(*head).unwrap_or(i64::MAX)],
+                )?;
+            }
+            HistoryChange::Cleared { checker } => {
+                tx.execute("DELETE FROM statuses WHERE checker = ?1", params![checker])?;
+            }
+            HistoryChange::Removed { checker } => {
+                tx.execute("DELETE FROM specs WHERE checker = ?1", params![checker])?;
+                tx.execute("DELETE FROM statuses WHERE checker = ?1", params![checker])?;
+            }
+        }
+    }
+    tx.commit()
+}
+
+fn load(connection: &Connection) -> Result<Checkers, Box<dyn Error + Send + Sync>> {
+    let mut checkers = Checkers::new();
+    let mut specs = connection.prepare(
+        "SELECT checker, description, url, \"group\", enabled, channel_capacity, history_capacity,
+                tags, history_head, history_len
+         FROM specs",
+    )?;
+    let mut statuses = connection.prepare(
+        "SELECT time, message, health, response_time_ns, maintenance FROM statuses
+         WHERE checker = ?1 ORDER BY time",
+    )?;
+    let mut rows = specs.query([])?;
+    while let Some(row) = rows.next()? {
+        let name: String = row.get(0)?;
+        let spec = checker::Spec {
+            description: row.get(1)?,
+            url: row.get(2)?,
+            group: row.get(3)?,
+            enabled: row.get(4)?,
+            channel_capacity: row.get(5)?,
+            history_len: row.get(9)?,
+            tags: row
+                .get::<_, String>(7)?
+                .split(',')
+                .filter(|tag| !tag.is_empty())
+                .map(ToString::to_string)
+                .collect(),
+        };
+        let capacity: usize = row.get(6)?;
+        let head: Option<i64> = row.get(8)?;
+        let mut history = VecDeque::new();
+        let mut status_rows = statuses.query(params![name])?;
+        while let Some(row) = status_rows.next()? {
+            let time: String = row.get(0)?;
+            let time = DateTime::parse_from_rfc3339(&time)?.with_timezone(&Local);
+            let health: String = row.get(2)?;
+            let mut status = checker::Status::up(row.get::<_, String>(1)?)
+                .with_health(parse_health(&health)?)
+                .with_maintenance(row.get(4)?);
+            if let Some(ns) = row.get::<_, Option<i64>>(3)? {
+                status = status.with_response_time(std::time::Duration::from_nanos(
+                    u64::try_from(ns).unwrap_or(0),
+                ));
+            }
+            history.push_back((time, status));
+        }
+        let mut history = StatusRingBuffer::from(history);
+        // Statuses recorded since the last save can go past the capacity, until it drops the
+        // oldest ones.
+        history.truncate_fifo(capacity);
+        if let Some(head) = head {
+            history.set_head(u64::try_from(head).unwrap_or(0));
+        }
+        let history: History = Box::new(history);
+        checkers.insert(name, checker::Checker::new(spec, history));
+    }
+    Ok(checkers)
+}
+
+#[async_trait]
+impl Persistence for SqlitePersistence {
+    async fn save(&mut self, checkers: &Checkers) -> Result<(), Box<dyn Error + Send + Sync>> {
+        tokio::task::block_in_place(|| save(self.connection()?, checkers))?;
+        self.out_of_sync = false;
+        Ok(())
+    }
+
+    async fn load(&mut self) -> Result<Checkers, Box<dyn Error + Send + Sync>> {
+        tokio::task::block_in_place(|| load(self.connection()?))
+    }
+
+    fn records_statuses(&self) -> bool {
+        !self.out_of_sync
+    }
+
+    async fn record(
+        &mut self,
+        changes: &[HistoryChange],
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if self.out_of_sync {
+            // The next save writes the statuses whole, these included.
+            return Ok(());
+        }
+        tokio::task::block_in_place(|| record(self.connection()?, changes)).inspect_err(|_| {
+            self.out_of_sync = true;
+        })?;
+        Ok(())
+    }
+
+    async fn save_specs(
+        &mut self,
+        specs: &BTreeMap<String, SavedSpec>,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        tokio::task::block_in_place(|| Ok(save_specs(self.connection()?, specs)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{AppState, Damping};
+    use crate::history::{HistoryBackend, OutOfOrderPolicy};
+
+    /// What should survive a restart, to compare restored checkers with those in memory.
+    fn summary(checkers: &Checkers) -> Vec<(String, serde_json::Value, usize, u64)> {
+        checkers
+            .iter()
+            .map(|(name, checker)| {
+                (
+                    name.clone(),
+                    serde_json::to_value(checker).expect("Failed to serialize"),
+                    checker.statuses.capacity(),
+                    checker.statuses.head_index(),
+                )
+            })
+            .collect()
+    }
+
+    fn push(app_state: &mut AppState, name: &str, messages: &[&str]) {
+        let checker = app_state.get_checker_with_sender_mut(name).unwrap();
+        for message in messages {
+            checker
+                .add_status(checker::Status::up(*message), OutOfOrderPolicy::Reject)
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_record_changes() {
+        let path = std::env::temp_dir().join(format!("swec-test-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let mut persistence = SqlitePersistence::new(path.clone());
+        let connection = persistence.connection().unwrap();
+
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let mut app_state = AppState::new(
+            BTreeMap::new(),
+            HistoryBackend::default(),
+            3,
+            None,
+            Damping::default(),
+            16,
+            64,
+        );
+        app_state.record_history_changes(sender);
+        let spec = |description: &str| checker::Spec::new(description.to_string(), None, None);
+        for name in ["a", "b", "c"] {
+            app_state.add_checker(name.to_string(), spec(name)).unwrap();
+        }
+        save_specs(connection, &app_state.get_saved_specs()).unwrap();
+
+        // Evicts the first two statuses of a.
+        push(&mut app_state, "a", &["1", "2", "3", "4", "5"]);
+        push(&mut app_state, "b", &["1", "2"]);
+        app_state
+            .get_checker_with_sender_mut("b")
+            .unwrap()
+            .clear_statuses();
+        push(&mut app_state, "b", &["3"]);
+        push(&mut app_state, "c", &["1"]);
+        app_state.remove_checker("c").unwrap();
+        let changes: Vec<_> = std::iter::from_fn(|| receiver.try_recv().ok()).collect();
+        record(connection, &changes).unwrap();
+
+        // As if the server crashed before saving the specs: the statuses are all there, and
+        // those evicted are dropped on load.
+        assert_eq!(
+            summary(&load(connection).unwrap()),
+            summary(&app_state.get_checkers())
+        );
+
+        app_state
+            .get_checker_with_sender_mut("a")
+            .unwrap()
+            .update_spec(spec("Updated"), 3);
+        save_specs(connection, &app_state.get_saved_specs()).unwrap();
+        let stored: usize = connection
+            .query_row(
+                "SELECT COUNT(*) FROM statuses WHERE checker = 'a'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(stored, 3, "The evicted statuses should be deleted");
+        let restored = load(connection).unwrap();
+        assert_eq!(restored["a"].spec.description, "Updated");
+        assert_eq!(summary(&restored), summary(&app_state.get_checkers()));
+
+        drop(persistence);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,6 +1,9 @@
+use crate::history::{
+    History, HistoryBackend, Iter, OutOfOrderPolicy, OutOfOrderStatus, StatusHistory,
+};
 use core::fmt::{self, Debug, Formatter};
-use serde::{Deserialize, Serialize};
-use std::{cmp::Ordering, collections::VecDeque};
+use serde::{ser::SerializeStruct, Deserialize, Serialize};
+use std::{cmp::Ordering, collections::VecDeque, ops::Bound};
 use swec_core::{Status, StatusBuffer};
 
 /// A fixed length ring buffer that overwrites the oldest element when full.
@@ -10,6 +13,9 @@ pub struct RingBuffer<T> {
     // be more than our capacity.
     inner: VecDeque<T>,
     capacity: usize,
+    // The number of elements ever added, which is the index of the next one. It can't be less
+    // than the length, and is not serialized: dumps store it alongside (see `set_head`).
+    head: u64,
 }
 
 impl<T> RingBuffer<T> {
@@ -25,6 +31,7 @@ impl<T> RingBuffer<T> {
         Self {
             inner: VecDeque::with_capacity(capacity),
             capacity,
+            head: 0,
         }
     }
 
@@ -42,6 +49,7 @@ impl<T> RingBuffer<T> {
             self.inner.pop_front();
         }
         self.inner.push_back(elem);
+        self.head += 1;
     }
 
     /// Add multiple elements to the ring buffer, overwriting the oldest elements if full.
@@ -58,6 +66,65 @@ impl<T> RingBuffer<T> {
         }
     }
 
+    /// Insert an element after the last element whose key is less than or equal to its key,
+    /// overwriting the oldest element if full. This keeps a buffer sorted by key sorted.
+    /// If the buffer is full and the element would be inserted before the oldest element, it is
+    /// dropped instead. Returns whether the element was inserted.
+    /// # Example
+    /// ```
+    /// # use swec::checker::RingBuffer;
+    /// let mut rb = RingBuffer::<i32>::new(3);
+    /// rb.push_multiple([1, 3]);
+    /// assert!(rb.insert_sorted_by_key(2, |x| *x));
+    /// assert_eq!(rb.iter().copied().collect::<Vec<_>>(), vec![1, 2, 3]);
+    /// assert!(!rb.insert_sorted_by_key(0, |x| *x));
+    /// assert_eq!(rb.iter().copied().collect::<Vec<_>>(), vec![1, 2, 3]);
+    /// ```
+    #[must_use = "the element is dropped if it is older than everything in a full buffer"]
+    pub fn insert_sorted_by_key<K: Ord>(&mut self, elem: T, mut key: impl FnMut(&T) -> K) -> bool {
+        let elem_key = key(&elem);
+        let index = self.inner.partition_point(|e| key(e) <= elem_key);
+        if self.inner.len() == self.capacity {
+            if index == 0 {
+                return false;
+            }
+            self.inner.pop_front();
+            self.inner.insert(index - 1, elem);
+        } else {
+            self.inner.insert(index, elem);
+        }
+        self.head += 1;
+        true
+    }
+
+    /// Keep only the elements matching the predicate, in order. The capacity is unchanged.
+    /// # Example
+    /// ```
+    /// # use swec::checker::RingBuffer;
+    /// let mut rb = RingBuffer::<i32>::new(5);
+    /// rb.push_multiple(1..=5);
+    /// rb.retain(|x| x % 2 == 1);
+    /// assert_eq!(rb.iter().copied().collect::<Vec<_>>(), vec![1, 3, 5]);
+    /// assert_eq!(rb.capacity(), 5);
+    /// ```
+    pub fn retain(&mut self, f: impl FnMut(&T) -> bool) {
+        self.inner.retain(f);
+    }
+
+    /// Remove all elements, keeping the capacity.
+    /// # Example
+    /// ```
+    /// # use swec::checker::RingBuffer;
+    /// let mut rb = RingBuffer::<i32>::new(5);
+    /// rb.push_multiple(1..=3);
+    /// rb.clear();
+    /// assert!(rb.is_empty());
+    /// assert_eq!(rb.capacity(), 5);
+    /// ```
+    pub fn clear(&mut self) {
+        self.inner.clear();
+    }
+
     /// Get an iterator over the elements in the ring buffer.
     /// The first element is the oldest, and the last element is the newest.
     /// # Example
@@ -69,10 +136,69 @@ impl<T> RingBuffer<T> {
     /// assert_eq!(iter.copied().collect::<Vec<_>>(), vec![8, 9, 10]);
     /// ```
     #[must_use]
-    pub fn iter(&self) -> std::collections::vec_deque::Iter<T> {
+    pub fn iter(&self) -> std::collections::vec_deque::Iter<'_, T> {
         self.inner.iter()
     }
 
+    /// Get the index the next element added will have. Elements are indexed in the order they are
+    /// added, and their indices don't change when older elements are overwritten, so that those
+    /// added since an index can be found again later with `iter_since`.
+    /// # Example
+    /// ```
+    /// # use swec::checker::RingBuffer;
+    /// let mut rb = RingBuffer::<i32>::new(3);
+    /// rb.push_multiple(1..=10);
+    /// assert_eq!(rb.head(), 10);
+    /// ```
+    #[must_use]
+    pub const fn head(&self) -> u64 {
+        self.head
+    }
+
+    /// Set the index the next element added will have, e.g. to restore the head of a buffer from
+    /// a dump. It can't be less than the length of the buffer.
+    /// # Example
+    /// ```
+    /// # use swec::checker::RingBuffer;
+    /// let mut rb = RingBuffer::<i32>::new(3);
+    /// rb.push_multiple(1..=3);
+    /// rb.set_head(10);
+    /// rb.push(4);
+    /// assert_eq!(rb.iter_since(10).copied().collect::<Vec<_>>(), vec![4]);
+    /// rb.set_head(0);
+    /// assert_eq!(rb.head(), 3);
+    /// ```
+    pub fn set_head(&mut self, head: u64) {
+        self.head = head.max(self.inner.len() as u64);
+    }
+
+    /// Get an iterator over the elements whose index is at least `index`, oldest first, or over
+    /// all elements if `index` is past the head, e.g. because the buffer was restored from a dump
+    /// since the index was taken.
+    /// Elements are assumed to be added at the end and removed from the start, as by `push`:
+    /// inserting an element before the newest one shifts the indices of those after it, so that
+    /// the elements since the previous head are the newest one again instead of the inserted one.
+    /// # Example
+    /// ```
+    /// # use swec::checker::RingBuffer;
+    /// let mut rb = RingBuffer::<i32>::new(3);
+    /// rb.push_multiple(1..=4);
+    /// let head = rb.head();
+    /// rb.push_multiple(5..=6);
+    /// assert_eq!(rb.iter_since(head).copied().collect::<Vec<_>>(), vec![5, 6]);
+    /// assert_eq!(rb.iter_since(0).copied().collect::<Vec<_>>(), vec![4, 5, 6]);
+    /// ```
+    #[must_use]
+    pub fn iter_since(&self, index: u64) -> std::collections::vec_deque::Iter<'_, T> {
+        let first = self.head - self.inner.len() as u64;
+        let skip = if index > self.head {
+            0
+        } else {
+            usize::try_from(index.saturating_sub(first)).unwrap_or(usize::MAX)
+        };
+        self.inner.range(skip.min(self.inner.len())..)
+    }
+
     /// Get the capacity of the ring buffer.
     #[must_use]
     pub const fn capacity(&self) -> usize {
@@ -147,6 +273,7 @@ impl<T> RingBuffer<T> {
     }
 }
 
+/// Serialized as `{"capacity": n, "items": [...]}`, so that the capacity survives a round-trip.
 impl<T> Serialize for RingBuffer<T>
 where
     T: Serialize,
@@ -155,10 +282,25 @@ where
     where
         S: serde::Serializer,
     {
-        self.inner.serialize(serializer)
+        let mut state = serializer.serialize_struct("RingBuffer", 2)?;
+        state.serialize_field("capacity", &self.capacity)?;
+        state.serialize_field("items", &self.inner)?;
+        state.end()
     }
 }
 
+/// The forms a ring buffer can be deserialized from.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SerializedRingBuffer<T> {
+    WithCapacity {
+        capacity: usize,
+        items: VecDeque<T>,
+    },
+    /// Ring buffers used to be serialized as a bare array, losing their capacity.
+    Bare(VecDeque<T>),
+}
+
 impl<'de, T> Deserialize<'de> for RingBuffer<T>
 where
     T: Deserialize<'de>,
@@ -167,14 +309,25 @@ where
     where
         D: serde::Deserializer<'de>,
     {
-        VecDeque::deserialize(deserializer).map(Self::from)
+        Ok(match SerializedRingBuffer::deserialize(deserializer)? {
+            SerializedRingBuffer::WithCapacity { capacity, items } => Self {
+                capacity: capacity.max(items.len()),
+                head: items.len() as u64,
+                inner: items,
+            },
+            SerializedRingBuffer::Bare(items) => Self::from(items),
+        })
     }
 }
 
 impl<T> From<VecDeque<T>> for RingBuffer<T> {
     fn from(inner: VecDeque<T>) -> Self {
         let capacity = inner.len(); // Not capacity: it may be more than length
-        Self { inner, capacity }
+        Self {
+            head: capacity as u64,
+            inner,
+            capacity,
+        }
     }
 }
 
@@ -222,6 +375,9 @@ impl Debug for ResizeError {
     }
 }
 
+/// A ring buffer of statuses.
+/// Statuses are always sorted by time, oldest first, which time-based features rely on: use
+/// `push_status` rather than `push` to add a status with an arbitrary time.
 pub type StatusRingBuffer = RingBuffer<(chrono::DateTime<chrono::Local>, Status)>;
 
 impl StatusBuffer for StatusRingBuffer {
@@ -238,6 +394,14 @@ impl StatusBuffer for StatusRingBuffer {
         self.len()
     }
 
+    fn head_index(&self) -> u64 {
+        self.head()
+    }
+
+    fn prune_before(&mut self, time: chrono::DateTime<chrono::Local>) {
+        self.retain(|(t, _)| *t >= time);
+    }
+
     fn from_vec(vec: Vec<(chrono::DateTime<chrono::Local>, Status)>) -> Self {
         Self::from(VecDeque::from(vec))
     }
@@ -247,6 +411,100 @@ impl StatusBuffer for StatusRingBuffer {
     }
 }
 
+impl StatusHistory for StatusRingBuffer {
+    fn backend(&self) -> HistoryBackend {
+        HistoryBackend::RingBuffer
+    }
+
+    fn clone_box(&self) -> History {
+        Box::new(self.clone())
+    }
+
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    fn truncate_fifo(&mut self, capacity: usize) {
+        RingBuffer::truncate_fifo(self, capacity);
+    }
+
+    fn set_head(&mut self, head: u64) {
+        RingBuffer::set_head(self, head);
+    }
+
+    fn clear(&mut self) {
+        RingBuffer::clear(self);
+    }
+
+    fn iter(&self) -> Iter<'_> {
+        Box::new(self.inner.iter().map(|(time, status)| (*time, status)))
+    }
+
+    fn statuses_in(
+        &self,
+        (since, until): (
+            Bound<chrono::DateTime<chrono::Local>>,
+            Bound<chrono::DateTime<chrono::Local>>,
+        ),
+    ) -> Iter<'_> {
+        // The statuses are sorted by time, so the bounds can be found by binary search.
+        let start = match since {
+            Bound::Included(since) => self.inner.partition_point(|(time, _)| *time < since),
+            Bound::Excluded(since) => self.inner.partition_point(|(time, _)| *time <= since),
+            Bound::Unbounded => 0,
+        };
+        let end = match until {
+            Bound::Included(until) => self.inner.partition_point(|(time, _)| *time <= until),
+            Bound::Excluded(until) => self.inner.partition_point(|(time, _)| *time < until),
+            Bound::Unbounded => self.inner.len(),
+        };
+        Box::new(
+            self.inner
+                .range(start..end.max(start))
+                .map(|(time, status)| (*time, status)),
+        )
+    }
+
+    fn iter_since(&self, index: u64) -> Iter<'_> {
+        Box::new(RingBuffer::iter_since(self, index).map(|(time, status)| (*time, status)))
+    }
+
+    fn push_status(
+        &mut self,
+        status: (chrono::DateTime<chrono::Local>, Status),
+        policy: OutOfOrderPolicy,
+    ) -> Result<(), OutOfOrderStatus> {
+        self.check_order(status.0, policy)?;
+        let time = status.0;
+        let newest = self.newest_time();
+        if self.insert_sorted_by_key(status, |(time, _)| *time) {
+            Ok(())
+        } else {
+            Err(OutOfOrderStatus {
+                time,
+                newest: newest.unwrap_or(time),
+            })
+        }
+    }
+
+    fn newest_time(&self) -> Option<chrono::DateTime<chrono::Local>> {
+        self.inner.back().map(|(time, _)| *time)
+    }
+
+    /// Only the oldest statuses are looked at, since the history is sorted.
+    fn evict_older_than(&mut self, max_age: chrono::Duration) {
+        let Some(cutoff) = self
+            .newest_time()
+            .and_then(|newest| newest.checked_sub_signed(max_age))
+        else {
+            return;
+        };
+        while self.inner.front().is_some_and(|(time, _)| *time < cutoff) {
+            self.inner.pop_front();
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -288,11 +546,135 @@ mod tests {
         assert_eq!(rb.iter().copied().collect::<Vec<_>>(), vec![6, 7, 8, 9, 10]);
     }
 
+    #[test]
+    fn test_insert_sorted_by_key() {
+        let mut rb = RingBuffer::<i32>::new(4);
+        rb.push_multiple([2, 4, 6]);
+        assert!(rb.insert_sorted_by_key(5, |x| *x));
+        assert_eq!(rb.iter().copied().collect::<Vec<_>>(), vec![2, 4, 5, 6]);
+        assert!(rb.insert_sorted_by_key(3, |x| *x));
+        assert_eq!(rb.iter().copied().collect::<Vec<_>>(), vec![3, 4, 5, 6]);
+        assert!(!rb.insert_sorted_by_key(1, |x| *x));
+        assert_eq!(rb.iter().copied().collect::<Vec<_>>(), vec![3, 4, 5, 6]);
+        assert!(rb.insert_sorted_by_key(7, |x| *x));
+        assert_eq!(rb.iter().copied().collect::<Vec<_>>(), vec![4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut rb = RingBuffer::<i32>::new(5);
+        rb.push_multiple(1..=10);
+        rb.retain(|x| *x > 7);
+        assert_eq!(rb.iter().copied().collect::<Vec<_>>(), vec![8, 9, 10]);
+        assert_eq!(rb.len(), 3);
+        rb.push_multiple(11..=13);
+        assert_eq!(
+            rb.iter().copied().collect::<Vec<_>>(),
+            vec![9, 10, 11, 12, 13]
+        );
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut rb = RingBuffer::<i32>::new(3);
+        rb.push_multiple(1..=5);
+        rb.clear();
+        assert!(rb.is_empty());
+        rb.push_multiple(6..=9);
+        assert_eq!(rb.iter().copied().collect::<Vec<_>>(), vec![7, 8, 9]);
+    }
+
+    #[test]
+    fn test_iter_since() {
+        let mut rb = RingBuffer::<i32>::new(3);
+        assert_eq!(rb.iter_since(0).count(), 0);
+        rb.push_multiple(1..=2);
+        assert_eq!(rb.iter_since(1).copied().collect::<Vec<_>>(), vec![2]);
+        assert_eq!(rb.iter_since(2).count(), 0);
+        rb.push_multiple(3..=7);
+        assert_eq!(rb.head(), 7);
+        assert_eq!(rb.iter_since(5).copied().collect::<Vec<_>>(), vec![6, 7]);
+        // Overwritten elements are skipped.
+        assert_eq!(rb.iter_since(1).copied().collect::<Vec<_>>(), vec![5, 6, 7]);
+        // Past the head, e.g. from before a restart.
+        assert_eq!(
+            rb.iter_since(42).copied().collect::<Vec<_>>(),
+            vec![5, 6, 7]
+        );
+        rb.clear();
+        assert_eq!(rb.head(), 7);
+        rb.push(8);
+        assert_eq!(rb.iter_since(7).copied().collect::<Vec<_>>(), vec![8]);
+    }
+
+    #[test]
+    fn test_head_survives_resizing() {
+        let mut rb = RingBuffer::<i32>::new(3);
+        rb.push_multiple(1..=5);
+        rb.resize(4).unwrap();
+        assert_eq!(rb.head(), 5);
+        rb.truncate_fifo(2);
+        assert_eq!(rb.head(), 5);
+        assert_eq!(rb.iter_since(4).copied().collect::<Vec<_>>(), vec![5]);
+    }
+
     #[test]
     fn test_serialize() {
-        let rb = RingBuffer::<i32>::new(5);
+        let mut rb = RingBuffer::<i32>::new(5);
+        let serialized = serde_json::to_string(&rb).expect("Failed to serialize");
+        assert_eq!(serialized, r#"{"capacity":5,"items":[]}"#);
+        rb.push_multiple(1..=2);
         let serialized = serde_json::to_string(&rb).expect("Failed to serialize");
-        assert_eq!(serialized, "[]");
+        assert_eq!(serialized, r#"{"capacity":5,"items":[1,2]}"#);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut rb = RingBuffer::<i32>::new(5);
+        rb.push_multiple(1..=3);
+        let serialized = serde_json::to_string(&rb).expect("Failed to serialize");
+        let rb: RingBuffer<i32> = serde_json::from_str(&serialized).expect("Failed to deserialize");
+        assert_eq!(rb.iter().copied().collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(rb.capacity(), 5);
+    }
+
+    #[test]
+    fn test_evict_older_than() {
+        let t0 = chrono::Local::now();
+        let seconds = |secs| chrono::Duration::try_seconds(secs).expect("Duration out of range");
+        let at = |secs| t0 + seconds(secs);
+        let mut rb = StatusRingBuffer::new(10);
+        rb.push((at(0), Status::up("")));
+        rb.push((at(10), Status::up("")));
+        rb.push((at(20), Status::up("")));
+        rb.evict_older_than(seconds(10));
+        let times: Vec<_> = rb.iter().map(|(time, _)| *time).collect();
+        assert_eq!(times, vec![at(10), at(20)]);
+        assert_eq!(rb.capacity(), 10);
+        rb.evict_older_than(seconds(0));
+        assert_eq!(rb.len(), 1);
+    }
+
+    #[test]
+    fn test_push_status_too_old_for_full_history() {
+        let t0 = chrono::Local::now();
+        let at = |secs| t0 + chrono::Duration::try_seconds(secs).expect("Duration out of range");
+        let mut rb = StatusRingBuffer::new(2);
+        for secs in [10, 20] {
+            rb.push_status((at(secs), Status::up("")), OutOfOrderPolicy::Insert)
+                .expect("Statuses in order are always added");
+        }
+        let err = rb
+            .push_status((at(5), Status::up("")), OutOfOrderPolicy::Insert)
+            .expect_err("The status would be dropped right away");
+        assert_eq!((err.time, err.newest), (at(5), at(20)));
+        assert_eq!(rb.len(), 2);
+        assert_eq!(rb.head(), 2);
+        // With room left, it is inserted.
+        rb.truncate_fifo(3);
+        rb.push_status((at(5), Status::up("")), OutOfOrderPolicy::Insert)
+            .expect("There is room for the status");
+        assert_eq!(rb.iter().next().map(|(time, _)| *time), Some(at(5)));
     }
 
     #[test]
@@ -306,6 +688,70 @@ mod tests {
         assert_eq!(rb.capacity(), 7);
     }
 
+    #[test]
+    fn test_downsample() {
+        let t0 = chrono::DateTime::from_timestamp(6000, 0)
+            .expect("Timestamp out of range")
+            .with_timezone(&chrono::Local);
+        let seconds = |secs| chrono::Duration::try_seconds(secs).expect("Duration out of range");
+        let at = |secs| t0 + seconds(secs);
+        let mut rb = StatusRingBuffer::new(10);
+        rb.push((at(0), Status::up("a")));
+        rb.push((at(10), Status::degraded("b")));
+        rb.push((at(20), Status::down("c")));
+        rb.push((at(50), Status::down("d")));
+        rb.push((at(60), Status::up("e")));
+        rb.push((at(180), Status::up("f")));
+        let rollup: Vec<_> = rb
+            .downsample(seconds(60))
+            .into_iter()
+            .map(|(time, status)| (time, status.message))
+            .collect();
+        assert_eq!(
+            rollup,
+            vec![
+                (at(0), "c".to_string()),
+                (at(60), "e".to_string()),
+                (at(180), "f".to_string()),
+            ]
+        );
+        assert_eq!(rb.len(), 6);
+    }
+
+    #[test]
+    fn test_uptime() {
+        let t0 = chrono::Local::now();
+        let seconds = |secs| chrono::Duration::try_seconds(secs).expect("Duration out of range");
+        let at = |secs| t0 + seconds(secs);
+        let mut rb = StatusRingBuffer::new(10);
+        rb.push((at(0), Status::up("")));
+        rb.push((at(10), Status::down("")));
+        rb.push((at(20), Status::up("")));
+        rb.push((at(1000), Status::down("")));
+
+        let uptime = rb.uptime(None, None, at(1010), Some(seconds(60)));
+        assert!((uptime.up_secs - 10.0).abs() < f64::EPSILON);
+        assert!((uptime.down_secs - 20.0).abs() < f64::EPSILON);
+        assert!((uptime.unknown_secs - 980.0).abs() < f64::EPSILON);
+        assert_eq!(uptime.samples, 4);
+
+        // Without a maximum gap, the last known state lasts until the next status.
+        let uptime = rb.uptime(Some(at(-5)), Some(at(15)), at(1010), None);
+        assert!((uptime.up_secs - 10.0).abs() < f64::EPSILON);
+        assert!((uptime.down_secs - 5.0).abs() < f64::EPSILON);
+        assert!((uptime.unknown_secs - 5.0).abs() < f64::EPSILON);
+        assert_eq!(uptime.samples, 2);
+        let percent = uptime.percent.expect("Some time is known");
+        assert!((percent - 200.0 / 3.0).abs() < 1e-9);
+
+        // Nothing is known before the first status.
+        let uptime = rb.uptime(Some(at(-20)), Some(at(-10)), at(1010), None);
+        assert_eq!(uptime.uptime, None);
+        let json = serde_json::to_value(&uptime).expect("Failed to serialize");
+        assert_eq!(json["uptime"], serde_json::Value::Null);
+        assert_eq!(json["samples"], 0);
+    }
+
     #[test]
     fn test_rev_iter() {
         let mut rb = RingBuffer::<i32>::new(5);